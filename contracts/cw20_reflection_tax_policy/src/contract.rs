@@ -0,0 +1,178 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    to_json_binary, Addr, Binary, Decimal, Deps, DepsMut, Env, MessageInfo, Order, Response,
+    StdError, StdResult,
+};
+use cw2::set_contract_version;
+use cw20_base::ContractError;
+use cw_storage_plus::{Item, Map};
+
+use crate::msg::{
+    ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, TaxOperation, TaxRates,
+};
+
+// version info for migration info
+const CONTRACT_NAME: &str = "choice:reflection-tax-policy";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub const ADMIN: Item<String> = Item::new("admin");
+pub const RATES: Item<TaxRates> = Item::new("rates");
+pub const OPERATION_RATES: Map<u8, (TaxOperation, TaxRates)> = Map::new("operation_rates");
+pub const RECIPIENT_RATES: Map<&Addr, TaxRates> = Map::new("recipient_rates");
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    deps.api.addr_validate(&msg.admin)?;
+    validate_rates(&msg.rates)?;
+    ADMIN.save(deps.storage, &msg.admin)?;
+    RATES.save(deps.storage, &msg.rates)?;
+
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    match msg {
+        ExecuteMsg::SetRates { rates } => {
+            validate_rates(&rates)?;
+            RATES.save(deps.storage, &rates)?;
+            Ok(Response::new().add_attribute("action", "set_rates"))
+        }
+        ExecuteMsg::SetOperationRates { operation, rates } => {
+            match rates {
+                Some(rates) => {
+                    validate_rates(&rates)?;
+                    OPERATION_RATES.save(
+                        deps.storage,
+                        operation_key(operation),
+                        &(operation, rates),
+                    )?;
+                }
+                None => OPERATION_RATES.remove(deps.storage, operation_key(operation)),
+            }
+            Ok(Response::new().add_attribute("action", "set_operation_rates"))
+        }
+        ExecuteMsg::SetRecipientRates { recipient, rates } => {
+            let recipient_addr = deps.api.addr_validate(&recipient)?;
+            match rates {
+                Some(rates) => {
+                    validate_rates(&rates)?;
+                    RECIPIENT_RATES.save(deps.storage, &recipient_addr, &rates)?;
+                }
+                None => RECIPIENT_RATES.remove(deps.storage, &recipient_addr),
+            }
+            Ok(Response::new()
+                .add_attribute("action", "set_recipient_rates")
+                .add_attribute("recipient", recipient))
+        }
+        ExecuteMsg::TransferAdmin { new_admin } => {
+            deps.api.addr_validate(&new_admin)?;
+            ADMIN.save(deps.storage, &new_admin)?;
+            Ok(Response::new()
+                .add_attribute("action", "transfer_admin")
+                .add_attribute("new_admin", new_admin))
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::TaxPolicy {
+            recipient,
+            operation,
+            ..
+        } => to_json_binary(&query_tax_policy(deps, recipient, operation)?),
+        QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
+    }
+}
+
+/// Recipient overrides win over operation overrides, which win over the default rates
+pub fn query_tax_policy(
+    deps: Deps,
+    recipient: Option<String>,
+    operation: TaxOperation,
+) -> StdResult<TaxRates> {
+    if let Some(recipient) = recipient {
+        let recipient_addr = deps.api.addr_validate(&recipient)?;
+        if let Some(rates) = RECIPIENT_RATES.may_load(deps.storage, &recipient_addr)? {
+            return Ok(rates);
+        }
+    }
+
+    if let Some((_, rates)) = OPERATION_RATES.may_load(deps.storage, operation_key(operation))? {
+        return Ok(rates);
+    }
+
+    RATES.load(deps.storage)
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    Ok(ConfigResponse {
+        admin: ADMIN.load(deps.storage)?,
+        rates: RATES.load(deps.storage)?,
+        operation_rates: OPERATION_RATES
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, entry)| entry))
+            .collect::<StdResult<_>>()?,
+        recipient_rates: RECIPIENT_RATES
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(addr, rates)| (addr.to_string(), rates)))
+            .collect::<StdResult<_>>()?,
+    })
+}
+
+fn operation_key(operation: TaxOperation) -> u8 {
+    match operation {
+        TaxOperation::Transfer => 0,
+        TaxOperation::Send => 1,
+        TaxOperation::TransferFrom => 2,
+        TaxOperation::SendFrom => 3,
+    }
+}
+
+/// Same bounds the token enforces on its built-in rates
+fn validate_rates(rates: &TaxRates) -> Result<(), ContractError> {
+    if rates.tax_rate > Decimal::one() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "tax_rate must be <= 1",
+        )));
+    }
+
+    if rates.reflection_rate + rates.burn_rate > Decimal::one() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "addition of reflection_rate & burn_rate must be <= 1",
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn ensure_admin(deps: &DepsMut, info: &MessageInfo) -> Result<Response, ContractError> {
+    let admin = ADMIN.may_load(deps.storage)?.unwrap_or_default();
+    if info.sender.to_string() != admin {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Unauthorized: not admin",
+        )));
+    }
+
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    Ok(Response::default())
+}
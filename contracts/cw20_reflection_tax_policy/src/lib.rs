@@ -0,0 +1,4 @@
+pub mod contract;
+pub mod msg;
+#[cfg(test)]
+mod test;
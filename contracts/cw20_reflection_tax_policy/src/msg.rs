@@ -0,0 +1,77 @@
+use cosmwasm_std::{BlockInfo, Decimal, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct InstantiateMsg {
+    pub admin: String,
+    pub rates: TaxRates,
+}
+
+/// Tax rate and split returned to the token, with the same meaning as its `SetTaxRate` rates
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct TaxRates {
+    pub tax_rate: Decimal,
+    pub reflection_rate: Decimal,
+    pub burn_rate: Decimal,
+}
+
+/// Kind of token movement being taxed
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxOperation {
+    Transfer,
+    Send,
+    TransferFrom,
+    SendFrom,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Rates applied when no override matches
+    SetRates {
+        rates: TaxRates,
+    },
+    /// Overrides the rates of one operation kind, `None` removes the override
+    SetOperationRates {
+        operation: TaxOperation,
+        rates: Option<TaxRates>,
+    },
+    /// Overrides the rates of transfers to `recipient` (e.g. a liquidity pair), `None` removes
+    /// the override. Takes precedence over operation overrides.
+    SetRecipientRates {
+        recipient: String,
+        rates: Option<TaxRates>,
+    },
+    TransferAdmin {
+        new_admin: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// Queried by the token on every taxed movement.
+    /// Return type: TaxRates.
+    TaxPolicy {
+        sender: Option<String>,
+        recipient: Option<String>,
+        amount: Uint128,
+        operation: TaxOperation,
+        block: BlockInfo,
+    },
+    /// Return type: ConfigResponse.
+    Config {},
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigResponse {
+    pub admin: String,
+    pub rates: TaxRates,
+    pub operation_rates: Vec<(TaxOperation, TaxRates)>,
+    pub recipient_rates: Vec<(String, TaxRates)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use crate::contract::{execute, instantiate, query};
+    use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, TaxOperation, TaxRates};
+    use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env};
+    use cosmwasm_std::{from_json, Decimal, Uint128};
+
+    fn rates(tax: u64) -> TaxRates {
+        TaxRates {
+            tax_rate: Decimal::percent(tax),
+            reflection_rate: Decimal::percent(50),
+            burn_rate: Decimal::zero(),
+        }
+    }
+
+    #[test]
+    fn test_recipient_and_operation_overrides() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = deps.api.addr_make("admin");
+        let pair = deps.api.addr_make("pair");
+        let user = deps.api.addr_make("user");
+
+        let admin_info = message_info(&admin, &[]);
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            admin_info.clone(),
+            InstantiateMsg {
+                admin: admin.to_string(),
+                rates: rates(5),
+            },
+        )
+        .unwrap();
+
+        let user_info = message_info(&user, &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            user_info,
+            ExecuteMsg::SetRates { rates: rates(1) },
+        );
+        assert!(res.is_err());
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info.clone(),
+            ExecuteMsg::SetOperationRates {
+                operation: TaxOperation::Send,
+                rates: Some(rates(3)),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info,
+            ExecuteMsg::SetRecipientRates {
+                recipient: pair.to_string(),
+                rates: Some(rates(12)),
+            },
+        )
+        .unwrap();
+
+        let policy = |recipient: &cosmwasm_std::Addr, operation: TaxOperation| -> Decimal {
+            let res: TaxRates = from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::TaxPolicy {
+                        sender: Some(user.to_string()),
+                        recipient: Some(recipient.to_string()),
+                        amount: Uint128::new(1_000),
+                        operation,
+                        block: env.block.clone(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            res.tax_rate
+        };
+
+        assert_eq!(policy(&user, TaxOperation::Transfer), Decimal::percent(5));
+        assert_eq!(policy(&user, TaxOperation::Send), Decimal::percent(3));
+        assert_eq!(policy(&pair, TaxOperation::Send), Decimal::percent(12));
+    }
+}
@@ -4,12 +4,18 @@ use std::str::FromStr;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, to_json_binary, Addr, Binary, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo, Reply,
-    Response, StdError, StdResult, Storage, SubMsg, SubMsgResponse, Uint128, WasmMsg,
+    attr, from_json, to_json_binary, to_json_vec, Addr, Attribute, Binary, BlockInfo, CosmosMsg,
+    Decimal, Decimal256, Deps, DepsMut, Env, MessageInfo, Order, QueryRequest, Reply, Response,
+    StdError, StdResult, Storage, SubMsg, SubMsgResponse, SystemResult, Uint128, Uint256, WasmMsg,
+    WasmQuery,
 };
 
+use choice::asset::AssetInfo;
+use choice::pair::{PoolResponse, QueryMsg as PairQueryMsg};
 use cw2::set_contract_version;
-use cw20::{Cw20ReceiveMsg, Logo, LogoInfo, MarketingInfoResponse};
+use cw20::{
+    AllowanceResponse, BalanceResponse, Cw20ReceiveMsg, Logo, LogoInfo, MarketingInfoResponse,
+};
 use cw20_base::allowances::{
     deduct_allowance, execute_burn_from, execute_decrease_allowance, execute_increase_allowance,
     query_allowance,
@@ -21,17 +27,33 @@ use cw20_base::contract::{
 use cw20_base::enumerable::{query_all_accounts, query_owner_allowances};
 
 use crate::msg::{
-    ExecuteMsg, GetTreasuryResponse, InstantiateMsg, MigrateMsg, QueryMsg, QueryTaxResponse,
-    TreasuryExecuteMsg, TreasuryInstantiateMsg,
+    AntiwhaleMode, CircuitBreaker, CircuitBreakerResponse, DividendsResponse, ExecuteMsg,
+    GetTaxPolicyResponse, GetTreasuryResponse, HolderTierResponse, InstantiateMsg, MevGuard,
+    MigrateMsg, PairsResponse, PermitNonceResponse, PermitPayload, QueryMsg, QueryTaxResponse,
+    RebateTier, RelayAction, RelayPayload, SellTaxTier, SellWindow, TaxDestinationInfo,
+    TaxDestinationMsg, TaxDestinationsResponse, TaxOperation, TaxPolicyQueryMsg, TaxPolicyResponse,
+    TaxStatsResponse, TotalSupplyResponse, TreasuryExecuteMsg, TreasuryInstantiateMsg,
+    TreasuryLpLockResponse, TreasuryQueryMsg, TreasuryRatesResponse, VestingResponse,
+    VestingSchedule,
+};
+use cw20_base::state::{
+    MinterData, TokenInfo, ALLOWANCES, ALLOWANCES_SPENDER, BALANCES, LOGO, MARKETING_INFO,
+    TOKEN_INFO,
 };
-use cw20_base::state::{MinterData, TokenInfo, BALANCES, LOGO, MARKETING_INFO, TOKEN_INFO};
 use cw20_base::ContractError;
-use cw_storage_plus::{Item, Map};
+use cw_storage_plus::{Bound, Item, Map};
+use ed25519_zebra::{Signature, VerificationKey};
+use serde::{Deserialize, Serialize};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "choice:reflection";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const SECONDS_PER_DAY: u64 = 86_400;
+const MAX_TAX_DESTINATIONS: usize = 10;
+/// Largest tax policy response accepted, anything bigger falls back to the built-in rates
+const MAX_POLICY_RESPONSE_BYTES: usize = 256;
+
 pub const TAX_RATE: Item<Decimal> = Item::new("tax_rate");
 pub const REFLECTION_RATE: Item<Decimal> = Item::new("reflection_rate");
 pub const BURN_RATE: Item<Decimal> = Item::new("burn_rate");
@@ -43,6 +65,138 @@ pub const TREASURY: Item<String> = Item::new("treasury");
 pub const WHITELIST: Map<String, bool> = Map::new("whitelist");
 pub const AGGREGATORS: Map<&Addr, bool> = Map::new("aggregators");
 pub const TRANSFER_FROM_RECIPIENT_WHITELIST: Map<&Addr, bool> = Map::new("tf_rcpt_wl");
+pub const TAX_STATS: Item<TaxStats> = Item::new("tax_stats");
+pub const HOLDER_TAX_PAID: Map<&Addr, Uint128> = Map::new("holder_tax_paid");
+pub const HOLDER_SINCE: Map<&Addr, u64> = Map::new("holder_since");
+pub const REBATE_TIERS: Item<Vec<RebateTier>> = Item::new("rebate_tiers");
+// Height from which balances and total supply are checkpointed, unset when snapshots are disabled
+pub const SNAPSHOT_START: Item<u64> = Item::new("snapshot_start");
+pub const BALANCE_CHECKPOINTS: Map<(&Addr, u64), Uint128> = Map::new("balance_checkpoints");
+pub const SUPPLY_CHECKPOINTS: Map<u64, Uint128> = Map::new("supply_checkpoints");
+// Reward token accrued per token held, raised whenever the treasury distributes rewards
+pub const DIVIDEND_INDEX: Item<Decimal256> = Item::new("dividend_index");
+// Sum of the balances earning dividends
+pub const DIVIDEND_SUPPLY: Item<Uint128> = Item::new("dividend_supply");
+// Rewards not spread over the holders yet, because nobody was eligible or the share per token
+// was below the index precision, carried into the next distribution
+pub const UNDISTRIBUTED_DIVIDENDS: Item<Uint128> = Item::new("undistributed_dividends");
+pub const DIVIDEND_EXCLUDED: Map<&Addr, bool> = Map::new("dividend_excluded");
+pub const HOLDER_DIVIDENDS: Map<&Addr, HolderDividend> = Map::new("holder_dividends");
+pub const PERMIT_KEYS: Map<&Addr, Binary> = Map::new("permit_keys");
+/// Next nonce expected in a signature from the owner
+pub const NONCES: Map<&Addr, u64> = Map::new("nonces");
+pub const VESTING: Map<&Addr, Vec<Vesting>> = Map::new("vesting");
+pub const TAX_POLICY: Item<Addr> = Item::new("tax_policy");
+/// Code ids a tax policy contract may be instantiated from
+pub const TAX_POLICY_CODES: Item<Vec<u64>> = Item::new("tax_policy_codes");
+/// Split of the tax credited to the treasury since its last liquify
+pub const TREASURY_SPLIT: Item<TreasurySplit> = Item::new("treasury_split");
+pub const IMMEDIATE_BURN: Item<bool> = Item::new("immediate_burn");
+pub const PAIRS: Map<&Addr, bool> = Map::new("pairs");
+pub const ANTIWHALE_MODE: Item<AntiwhaleMode> = Item::new("antiwhale_mode");
+pub const SELL_TAX_TIERS: Item<Vec<SellTaxTier>> = Item::new("sell_tax_tiers");
+pub const MEV_GUARD: Item<MevGuard> = Item::new("mev_guard");
+/// Block height of the last buy of an address from a registered pair
+pub const LAST_BUY: Map<&Addr, u64> = Map::new("last_buy");
+pub const CIRCUIT_BREAKER: Item<CircuitBreaker> = Item::new("circuit_breaker");
+pub const SELL_WINDOWS: Map<&Addr, SellWindowState> = Map::new("sell_windows");
+pub const TAX_DESTINATIONS: Item<Vec<TaxDestination>> = Item::new("tax_destinations");
+pub const DESTINATION_RECEIVED: Map<&Addr, Uint128> = Map::new("destination_received");
+
+/// Extra recipient of a share of every taxed amount
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TaxDestination {
+    pub address: Addr,
+    pub share: Decimal,
+}
+
+/// Tax credited to the treasury since its last liquify, and the parts of it meant for the
+/// reflection and burn legs
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct TreasurySplit {
+    pub total: Uint128,
+    pub reflection: Uint128,
+    pub burn: Uint128,
+}
+
+/// Volume sold into a pair during the current circuit breaker window
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SellWindowState {
+    pub start: u64,
+    pub volume: Uint128,
+    pub limit: Uint128,
+}
+
+impl SellWindowState {
+    /// No further sell fits in the window
+    pub fn tripped(&self) -> bool {
+        self.volume >= self.limit
+    }
+
+    /// Selling `amount` would take the window over its limit
+    pub fn exceeded_by(&self, amount: Uint128) -> bool {
+        self.volume + amount > self.limit
+    }
+}
+
+/// The movement being taxed, as seen by `query_tax`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaxContext {
+    pub sender: Option<Addr>,
+    pub recipient: Option<Addr>,
+    pub operation: TaxOperation,
+}
+
+impl TaxContext {
+    pub fn new(sender: &Addr, recipient: &Addr, operation: TaxOperation) -> Self {
+        TaxContext {
+            sender: Some(sender.clone()),
+            recipient: Some(recipient.clone()),
+            operation,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Vesting {
+    pub amount: Uint128,
+    pub start_time: u64,
+    pub cliff_time: u64,
+    pub end_time: u64,
+}
+
+impl Vesting {
+    /// Amount vested at block time `now`
+    pub fn vested(&self, now: u64) -> Uint128 {
+        if now < self.cliff_time {
+            Uint128::zero()
+        } else if now >= self.end_time {
+            self.amount
+        } else {
+            self.amount
+                .multiply_ratio(now - self.start_time, self.end_time - self.start_time)
+        }
+    }
+}
+
+/// Dividend checkpoint of a holder
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct HolderDividend {
+    /// Value of `DIVIDEND_INDEX` when the holder was last settled
+    pub index: Decimal256,
+    /// Rewards accrued and not yet claimed
+    pub pending: Uint128,
+}
+
+/// Lifetime counters of the taxes collected on transfers
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct TaxStats {
+    pub total_taxed: Uint128,
+    pub total_to_treasury: Uint128,
+    pub total_burned: Uint128,
+    pub total_reflected: Uint128,
+    pub taxed_transfer_count: u64,
+}
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -62,9 +216,37 @@ pub fn instantiate(
     BURN_RATE.save(deps.storage, &Decimal::zero())?;
     MAX_TRANSFER_SUPPLY_RATE.save(deps.storage, &Decimal::from_str("1")?)?;
     WHITELIST.save(deps.storage, info.sender.to_string(), &true)?;
+    DIVIDEND_SUPPLY.save(deps.storage, &Uint128::zero())?;
+    if msg.enable_snapshots.unwrap_or(false) {
+        SNAPSHOT_START.save(deps.storage, &env.block.height)?;
+    }
 
     // create initial accounts
     let total_supply = create_accounts(&mut deps, &msg.initial_balances)?;
+    for coin in msg.initial_balances.iter() {
+        let address = deps.api.addr_validate(&coin.address)?;
+        on_balance_change(
+            deps.storage,
+            &env.block,
+            &address,
+            Uint128::zero(),
+            coin.amount,
+        )?;
+    }
+    checkpoint_total_supply(deps.storage, &env.block, Uint128::zero(), total_supply)?;
+
+    for schedule in msg.vesting.iter().flatten() {
+        let address = deps.api.addr_validate(&schedule.address)?;
+        add_vesting(deps.storage, &address, schedule)?;
+        let balance = BALANCES
+            .may_load(deps.storage, &address)?
+            .unwrap_or_default();
+        if locked_amount(deps.storage, &address, env.block.time.seconds())? > balance {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Vesting amount exceeds initial balance",
+            )));
+        }
+    }
 
     if let Some(limit) = msg.get_cap() {
         if total_supply > limit {
@@ -141,13 +323,23 @@ pub fn execute(
         ExecuteMsg::Transfer { recipient, amount } => {
             execute_transfer(deps, env, info, recipient, amount)
         }
-        ExecuteMsg::Burn { amount } => execute_burn(deps, env, info, amount),
+        ExecuteMsg::Burn { amount } => {
+            let accounts = vec![info.sender.clone()];
+            with_balance_hooks(deps, env, accounts, |deps, env| {
+                execute_burn(deps, env, info, amount)
+            })
+        }
         ExecuteMsg::Send {
             contract,
             amount,
             msg,
         } => execute_send(deps, env, info, contract, amount, msg),
-        ExecuteMsg::Mint { recipient, amount } => execute_mint(deps, env, info, recipient, amount),
+        ExecuteMsg::Mint { recipient, amount } => {
+            let accounts = vec![deps.api.addr_validate(&recipient)?];
+            with_balance_hooks(deps, env, accounts, |deps, env| {
+                execute_mint(deps, env, info, recipient, amount)
+            })
+        }
         ExecuteMsg::IncreaseAllowance {
             spender,
             amount,
@@ -163,7 +355,12 @@ pub fn execute(
             recipient,
             amount,
         } => execute_transfer_from(deps, env, info, owner, recipient, amount),
-        ExecuteMsg::BurnFrom { owner, amount } => execute_burn_from(deps, env, info, owner, amount),
+        ExecuteMsg::BurnFrom { owner, amount } => {
+            let accounts = vec![deps.api.addr_validate(&owner)?];
+            with_balance_hooks(deps, env, accounts, |deps, env| {
+                execute_burn_from(deps, env, info, owner, amount)
+            })
+        }
         ExecuteMsg::SendFrom {
             owner,
             contract,
@@ -207,7 +404,7 @@ pub fn execute(
             if !AGGREGATORS.has(deps.storage, &info.sender) {
                 return Err(ContractError::Unauthorized {});
             }
-            execute_transfer_tax_exempt(deps, info, recipient, amount)
+            execute_transfer_tax_exempt(deps, env, info, recipient, amount)
         }
         ExecuteMsg::TaxExemptSend {
             contract,
@@ -217,7 +414,7 @@ pub fn execute(
             if !AGGREGATORS.has(deps.storage, &info.sender) {
                 return Err(ContractError::Unauthorized {});
             }
-            execute_send_tax_exempt(deps, info, contract, amount, msg)
+            execute_send_tax_exempt(deps, env, info, contract, amount, msg)
         }
 
         // To enable tax free liquidity provision
@@ -229,11 +426,80 @@ pub fn execute(
         }
 
         ExecuteMsg::TransferAdmin { new_admin } => transfer_admin(deps, info, new_admin),
+        ExecuteMsg::SetRebateTiers { tiers } => set_rebate_tiers(deps, info, tiers),
+        ExecuteMsg::SetSnapshots { enabled } => set_snapshots(deps, env, info, enabled),
+        ExecuteMsg::DistributeDividends { amount } => distribute_dividends(deps, info, amount),
+        ExecuteMsg::Claim {} => claim_dividends(deps, info),
+        ExecuteMsg::SetDividendExclusion { address, excluded } => {
+            set_dividend_exclusion(deps, info, address, excluded)
+        }
+        ExecuteMsg::RegisterPermitKey { public_key } => register_permit_key(deps, info, public_key),
+        ExecuteMsg::Permit {
+            owner,
+            spender,
+            amount,
+            expires,
+            nonce,
+            signature,
+        } => permit(
+            deps,
+            env.clone(),
+            // The domain fields bind the signature to this chain and token
+            PermitPayload {
+                chain_id: env.block.chain_id,
+                contract: env.contract.address.to_string(),
+                owner,
+                spender,
+                amount,
+                expires,
+                nonce,
+            },
+            signature,
+        ),
+        ExecuteMsg::CreateVesting { schedule } => create_vesting(deps, env, info, schedule),
+        ExecuteMsg::SetTaxPolicy { contract } => set_tax_policy(deps, info, contract),
+        ExecuteMsg::SetTaxPolicyCodes { code_ids } => set_tax_policy_codes(deps, info, code_ids),
+        ExecuteMsg::TreasuryLiquified {} => treasury_liquified(deps, info),
+        ExecuteMsg::RegisterPair { address } => register_pair(deps, info, address, true),
+        ExecuteMsg::DeregisterPair { address } => register_pair(deps, info, address, false),
+        ExecuteMsg::SetSellTaxTiers { tiers } => set_sell_tax_tiers(deps, info, tiers),
+        ExecuteMsg::SetAntiwhaleMode { mode } => set_antiwhale_mode(deps, info, mode),
+        ExecuteMsg::SetMevGuard { guard } => set_mev_guard(deps, info, guard),
+        ExecuteMsg::SetCircuitBreaker { breaker } => set_circuit_breaker(deps, info, breaker),
+        ExecuteMsg::ResetCircuitBreaker { pair } => reset_circuit_breaker(deps, info, pair),
+        ExecuteMsg::SetImmediateBurn { enabled } => set_immediate_burn(deps, info, enabled),
+        ExecuteMsg::SetTaxDestinations { destinations } => {
+            set_tax_destinations(deps, info, destinations)
+        }
+        ExecuteMsg::RelayTransfer {
+            owner,
+            action,
+            fee,
+            relayer,
+            nonce,
+            deadline,
+            signature,
+        } => relay_transfer(
+            deps,
+            env.clone(),
+            info,
+            RelayPayload {
+                chain_id: env.block.chain_id,
+                contract: env.contract.address.to_string(),
+                owner,
+                action,
+                fee,
+                relayer,
+                nonce,
+                deadline,
+            },
+            signature,
+        ),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Balance { address } => to_json_binary(&query_balance(deps, address)?),
         QueryMsg::TokenInfo {} => to_json_binary(&query_token_info(deps)?),
@@ -251,12 +517,50 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         }
         QueryMsg::MarketingInfo {} => to_json_binary(&query_marketing_info(deps)?),
         QueryMsg::DownloadLogo {} => to_json_binary(&query_download_logo(deps)?),
-        QueryMsg::QueryTax { amount } => to_json_binary(&query_tax(deps.storage, amount)?),
+        QueryMsg::QueryTax {
+            amount,
+            sender,
+            recipient,
+            operation,
+        } => {
+            let context = TaxContext {
+                sender: sender.map(|s| deps.api.addr_validate(&s)).transpose()?,
+                recipient: recipient.map(|r| deps.api.addr_validate(&r)).transpose()?,
+                operation: operation.unwrap_or(TaxOperation::Transfer),
+            };
+            to_json_binary(&query_tax(deps, &env, &context, amount)?)
+        }
+        QueryMsg::GetTaxPolicy {} => to_json_binary(&query_tax_policy_contract(deps.storage)?),
+        QueryMsg::Pairs {} => to_json_binary(&query_pairs(deps.storage)?),
+        QueryMsg::AntiwhaleMode {} => to_json_binary(
+            &ANTIWHALE_MODE
+                .may_load(deps.storage)?
+                .unwrap_or(AntiwhaleMode::TotalSupply {}),
+        ),
+        QueryMsg::MevGuard {} => to_json_binary(&MEV_GUARD.may_load(deps.storage)?),
+        QueryMsg::CircuitBreaker { pair } => {
+            to_json_binary(&query_circuit_breaker(deps, &env, pair)?)
+        }
+        QueryMsg::TaxDestinations {} => to_json_binary(&query_tax_destinations(deps.storage)?),
         QueryMsg::QueryRates {} => to_json_binary(&query_rate(deps.storage)?),
+        QueryMsg::TreasuryRates {} => to_json_binary(&query_treasury_rates(deps.storage)?),
         QueryMsg::GetWhitelist { address } => {
             to_json_binary(&query_whitelist(deps.storage, address)?)
         }
         QueryMsg::GetTreasury {} => to_json_binary(&query_treasury(deps.storage)?),
+        QueryMsg::TaxStats {} => to_json_binary(&query_tax_stats(deps.storage)?),
+        QueryMsg::HolderTier { address } => {
+            to_json_binary(&query_holder_tier(deps, &env, address)?)
+        }
+        QueryMsg::BalanceAtHeight { address, height } => {
+            to_json_binary(&query_balance_at_height(deps, &env, address, height)?)
+        }
+        QueryMsg::TotalSupplyAtHeight { height } => {
+            to_json_binary(&query_total_supply_at_height(deps, &env, height)?)
+        }
+        QueryMsg::Dividends { address } => to_json_binary(&query_dividends(deps, address)?),
+        QueryMsg::PermitNonce { owner } => to_json_binary(&query_permit_nonce(deps, owner)?),
+        QueryMsg::Vesting { address } => to_json_binary(&query_vesting(deps, env, address)?),
     }
 }
 
@@ -289,13 +593,13 @@ pub fn transfer_admin(
 /// Standard CW20 transfer function that is modified to include tax functions, and anti-whale feature
 /// These modifications are all applied to the `transfer`, `send`, `transfer_from`, and `send_from` functions
 pub fn execute_transfer(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     recipient: String,
     amount: Uint128,
 ) -> Result<Response, ContractError> {
-    ensure_antiwhale(&deps, info.sender.to_string(), amount)?;
+    ensure_antiwhale(&deps, &env, info.sender.to_string(), &recipient, amount)?;
 
     // If whitelisetd, we simply do not apply taxes
     let recipient_whitelist = WHITELIST
@@ -306,49 +610,22 @@ pub fn execute_transfer(
         .unwrap_or_default();
     let whitelisted = recipient_whitelist || sender_whitelist;
 
-    // Loads treasury addresses, and query for taxes on transfers
-    let treasury = TREASURY.may_load(deps.storage)?.unwrap_or_default();
+    // Query for taxes on transfers
     let rcpt_addr = deps.api.addr_validate(&recipient)?;
-    let taxes = query_tax(deps.storage, amount)?;
+    let context = TaxContext::new(&info.sender, &rcpt_addr, TaxOperation::Transfer);
+    let taxes = query_tax(deps.as_ref(), &env, &context, amount)?;
+    let guard_attrs = guard_trade(&mut deps, &env, &context, amount, &taxes, whitelisted)?;
     let outgoing_amount = if whitelisted { amount } else { taxes.after_tax };
 
-    BALANCES.update(
-        deps.storage,
-        &info.sender,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
-    BALANCES.update(
-        deps.storage,
-        &rcpt_addr,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default() + outgoing_amount)
-        },
-    )?;
+    decrease_balance(deps.storage, &env.block, &info.sender, amount)?;
+    increase_balance(deps.storage, &env.block, &rcpt_addr, outgoing_amount)?;
 
     let mut messages = vec![];
 
     // Assuming no whitelist, we apply taxes, and immediately add them to the treasury by modifying balance variables
     // We also send generate a transfer teransaction log under `TransferEvent` to ensure explorer tracks transfer properly
     if !whitelisted {
-        BALANCES.update(
-            deps.storage,
-            &deps.api.addr_validate(&treasury)?,
-            |balance: Option<Uint128>| -> StdResult<_> {
-                Ok(balance.unwrap_or_default() + taxes.taxed_amount)
-            },
-        )?;
-
-        messages.push(WasmMsg::Execute {
-            contract_addr: env.contract.address.to_string(),
-            msg: to_json_binary(&ExecuteMsg::TransferEvent {
-                from: info.sender.to_string(),
-                to: treasury.to_string(),
-                amount: taxes.taxed_amount,
-            })?,
-            funds: vec![],
-        })
+        messages.extend(collect_tax(&mut deps, &env, &info.sender, &taxes)?);
     }
 
     let res = Response::new()
@@ -357,19 +634,20 @@ pub fn execute_transfer(
         .add_attribute("from", info.sender)
         .add_attribute("to", recipient)
         .add_attribute("amount", outgoing_amount)
-        .add_attribute("post_tax_amount", outgoing_amount);
+        .add_attribute("post_tax_amount", outgoing_amount)
+        .add_attributes(guard_attrs);
     Ok(res)
 }
 
 pub fn execute_send(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     contract: String,
     amount: Uint128,
     msg: Binary,
 ) -> Result<Response, ContractError> {
-    ensure_antiwhale(&deps, info.sender.to_string(), amount)?;
+    ensure_antiwhale(&deps, &env, info.sender.to_string(), &contract, amount)?;
 
     let recipient_whitelist = WHITELIST
         .may_load(deps.storage, contract.clone())?
@@ -378,46 +656,19 @@ pub fn execute_send(
         .may_load(deps.storage, info.sender.to_string())?
         .unwrap_or_default();
     let whitelisted = recipient_whitelist || sender_whitelist;
-    let treasury = TREASURY.may_load(deps.storage)?.unwrap_or_default();
     let rcpt_addr = deps.api.addr_validate(&contract)?;
-    let taxes = query_tax(deps.storage, amount)?;
+    let context = TaxContext::new(&info.sender, &rcpt_addr, TaxOperation::Send);
+    let taxes = query_tax(deps.as_ref(), &env, &context, amount)?;
+    let guard_attrs = guard_trade(&mut deps, &env, &context, amount, &taxes, whitelisted)?;
     let outgoing_amount = if whitelisted { amount } else { taxes.after_tax };
 
     // move the tokens to the contract
-    BALANCES.update(
-        deps.storage,
-        &info.sender,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
-    BALANCES.update(
-        deps.storage,
-        &rcpt_addr,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default() + outgoing_amount)
-        },
-    )?;
+    decrease_balance(deps.storage, &env.block, &info.sender, amount)?;
+    increase_balance(deps.storage, &env.block, &rcpt_addr, outgoing_amount)?;
 
     let mut messages = vec![];
     if !whitelisted {
-        BALANCES.update(
-            deps.storage,
-            &deps.api.addr_validate(&treasury)?,
-            |balance: Option<Uint128>| -> StdResult<_> {
-                Ok(balance.unwrap_or_default() + taxes.taxed_amount)
-            },
-        )?;
-
-        messages.push(WasmMsg::Execute {
-            contract_addr: env.contract.address.to_string(),
-            msg: to_json_binary(&ExecuteMsg::TransferEvent {
-                from: info.sender.to_string(),
-                to: treasury.to_string(),
-                amount: taxes.taxed_amount,
-            })?,
-            funds: vec![],
-        })
+        messages.extend(collect_tax(&mut deps, &env, &info.sender, &taxes)?);
     }
 
     let res = Response::new()
@@ -427,6 +678,7 @@ pub fn execute_send(
         .add_attribute("to", &contract)
         .add_attribute("amount", outgoing_amount)
         .add_attribute("post_tax_amount", outgoing_amount)
+        .add_attributes(guard_attrs)
         .add_message(
             // We do not modify the send message, but we allow the hooked contract to calculate taxes against this contract
             Cw20ReceiveMsg {
@@ -440,14 +692,14 @@ pub fn execute_send(
 }
 
 pub fn execute_transfer_from(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     owner: String,
     recipient: String,
     amount: Uint128,
 ) -> Result<Response, ContractError> {
-    ensure_antiwhale(&deps, owner.clone(), amount)?;
+    ensure_antiwhale(&deps, &env, owner.clone(), &recipient, amount)?;
 
     let owner_whitelist = WHITELIST
         .may_load(deps.storage, owner.clone())?
@@ -468,63 +720,39 @@ pub fn execute_transfer_from(
     let whitelisted =
         owner_whitelist || recipient_whitelist || sender_whitelist || is_tf_recipient_whitelisted;
 
-    let treasury = TREASURY.may_load(deps.storage)?.unwrap_or_default();
     let owner_addr = deps.api.addr_validate(&owner)?;
-    let taxes = query_tax(deps.storage, amount)?;
+    let context = TaxContext::new(&owner_addr, &recipient_addr, TaxOperation::TransferFrom);
+    let taxes = query_tax(deps.as_ref(), &env, &context, amount)?;
+    let guard_attrs = guard_trade(&mut deps, &env, &context, amount, &taxes, whitelisted)?;
     let outgoing_amount = if whitelisted { amount } else { taxes.after_tax };
 
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
 
-    BALANCES.update(
-        deps.storage,
-        &owner_addr,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
-    BALANCES.update(
-        deps.storage,
-        &recipient_addr,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default() + outgoing_amount)
-        },
-    )?;
+    decrease_balance(deps.storage, &env.block, &owner_addr, amount)?;
+    increase_balance(deps.storage, &env.block, &recipient_addr, outgoing_amount)?;
 
     let mut messages = vec![];
     if !whitelisted {
-        BALANCES.update(
-            deps.storage,
-            &deps.api.addr_validate(&treasury)?,
-            |balance: Option<Uint128>| -> StdResult<_> {
-                Ok(balance.unwrap_or_default() + taxes.taxed_amount)
-            },
-        )?;
-
-        messages.push(WasmMsg::Execute {
-            contract_addr: env.contract.address.to_string(),
-            msg: to_json_binary(&ExecuteMsg::TransferEvent {
-                from: info.sender.to_string(),
-                to: treasury.to_string(),
-                amount: taxes.taxed_amount,
-            })?,
-            funds: vec![],
-        })
+        messages.extend(collect_tax(&mut deps, &env, &owner_addr, &taxes)?);
     }
 
-    let res = Response::new().add_messages(messages).add_attributes(vec![
-        attr("action", "transfer_from"),
-        attr("from", owner),
-        attr("to", recipient),
-        attr("by", info.sender),
-        attr("amount", outgoing_amount),
-        attr("post_tax_amount", outgoing_amount.to_string()),
-    ]);
+    let res = Response::new()
+        .add_messages(messages)
+        .add_attributes(vec![
+            attr("action", "transfer_from"),
+            attr("from", owner),
+            attr("to", recipient),
+            attr("by", info.sender),
+            attr("amount", outgoing_amount),
+            attr("post_tax_amount", outgoing_amount.to_string()),
+        ])
+        .add_attributes(guard_attrs);
     Ok(res)
 }
 
 pub fn execute_send_from(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     owner: String,
@@ -532,7 +760,7 @@ pub fn execute_send_from(
     amount: Uint128,
     msg: Binary,
 ) -> Result<Response, ContractError> {
-    ensure_antiwhale(&deps, info.sender.to_string(), amount)?;
+    ensure_antiwhale(&deps, &env, info.sender.to_string(), &contract, amount)?;
 
     let owner_whitelist = WHITELIST
         .may_load(deps.storage, owner.clone())?
@@ -546,51 +774,24 @@ pub fn execute_send_from(
 
     let whitelisted = owner_whitelist || recipient_whitelist || sender_whitelist;
 
-    let treasury = TREASURY.may_load(deps.storage)?.unwrap_or_default();
     let rcpt_addr = deps.api.addr_validate(&contract)?;
     let owner_addr = deps.api.addr_validate(&owner)?;
-    let taxes = query_tax(deps.storage, amount)?;
+    let context = TaxContext::new(&owner_addr, &rcpt_addr, TaxOperation::SendFrom);
+    let taxes = query_tax(deps.as_ref(), &env, &context, amount)?;
+    let guard_attrs = guard_trade(&mut deps, &env, &context, amount, &taxes, whitelisted)?;
     let outgoing_amount = if whitelisted { amount } else { taxes.after_tax };
 
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
 
     // move the tokens to the contract
-    BALANCES.update(
-        deps.storage,
-        &owner_addr,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
+    decrease_balance(deps.storage, &env.block, &owner_addr, amount)?;
 
-    BALANCES.update(
-        deps.storage,
-        &rcpt_addr,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default() + outgoing_amount)
-        },
-    )?;
+    increase_balance(deps.storage, &env.block, &rcpt_addr, outgoing_amount)?;
 
     let mut messages = vec![];
     if !whitelisted {
-        BALANCES.update(
-            deps.storage,
-            &deps.api.addr_validate(&treasury)?,
-            |balance: Option<Uint128>| -> StdResult<_> {
-                Ok(balance.unwrap_or_default() + taxes.taxed_amount)
-            },
-        )?;
-
-        messages.push(WasmMsg::Execute {
-            contract_addr: env.contract.address.to_string(),
-            msg: to_json_binary(&ExecuteMsg::TransferEvent {
-                from: info.sender.to_string(),
-                to: treasury.to_string(),
-                amount: taxes.taxed_amount,
-            })?,
-            funds: vec![],
-        })
+        messages.extend(collect_tax(&mut deps, &env, &owner_addr, &taxes)?);
     }
 
     let attrs = vec![
@@ -613,31 +814,342 @@ pub fn execute_send_from(
     let res = Response::new()
         .add_messages(messages)
         .add_message(msg)
-        .add_attributes(attrs);
+        .add_attributes(attrs)
+        .add_attributes(guard_attrs);
+    Ok(res)
+}
+
+/// Adds `amount` to the balance of `address`, returning the new balance
+pub fn increase_balance(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    address: &Addr,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let previous = BALANCES.may_load(storage, address)?.unwrap_or_default();
+    let balance = previous.checked_add(amount)?;
+    BALANCES.save(storage, address, &balance)?;
+    on_balance_change(storage, block, address, previous, balance)?;
+    Ok(balance)
+}
+
+/// Removes `amount` from the balance of `address`, returning the new balance
+pub fn decrease_balance(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    address: &Addr,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let previous = BALANCES.may_load(storage, address)?.unwrap_or_default();
+    let balance = previous.checked_sub(amount)?;
+    on_balance_change(storage, block, address, previous, balance)?;
+    BALANCES.save(storage, address, &balance)?;
+    Ok(balance)
+}
+
+/// Bookkeeping that has to follow every balance change of an account
+pub fn on_balance_change(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    address: &Addr,
+    previous: Uint128,
+    balance: Uint128,
+) -> StdResult<()> {
+    // Locked tokens count toward the balance but cannot leave it
+    if balance < previous && balance < locked_amount(storage, address, block.time.seconds())? {
+        return Err(StdError::generic_err(
+            "Amount exceeds the unlocked balance of the account",
+        ));
+    }
+
+    // Holding time starts with the first tokens received and is reset once the account is emptied
+    if balance.is_zero() {
+        HOLDER_SINCE.remove(storage, address);
+    } else if !HOLDER_SINCE.has(storage, address) {
+        HOLDER_SINCE.save(storage, address, &block.time.seconds())?;
+    }
+
+    if !DIVIDEND_EXCLUDED.has(storage, address) {
+        let holder = settle_dividends(storage, address, previous)?;
+        HOLDER_DIVIDENDS.save(storage, address, &holder)?;
+        let supply = DIVIDEND_SUPPLY.may_load(storage)?.unwrap_or_default();
+        let supply = if balance >= previous {
+            supply + (balance - previous)
+        } else {
+            supply.saturating_sub(previous - balance)
+        };
+        DIVIDEND_SUPPLY.save(storage, &supply)?;
+    }
+
+    if let Some(start) = SNAPSHOT_START.may_load(storage)? {
+        // The first change since snapshots were enabled also records the balance held until now
+        let last_checkpoint = BALANCE_CHECKPOINTS
+            .prefix(address)
+            .keys(storage, None, None, Order::Descending)
+            .next()
+            .transpose()?;
+        if last_checkpoint.is_none_or(|height| height < start) {
+            BALANCE_CHECKPOINTS.save(storage, (address, start), &previous)?;
+        }
+        BALANCE_CHECKPOINTS.save(storage, (address, block.height), &balance)?;
+    }
+    Ok(())
+}
+
+/// Accrues the dividends `address` earned on `balance` since its last checkpoint
+pub fn settle_dividends(
+    storage: &dyn Storage,
+    address: &Addr,
+    balance: Uint128,
+) -> StdResult<HolderDividend> {
+    let index = DIVIDEND_INDEX.may_load(storage)?.unwrap_or_default();
+    let mut holder = HOLDER_DIVIDENDS
+        .may_load(storage, address)?
+        .unwrap_or_default();
+    if index > holder.index {
+        holder.pending +=
+            Uint128::try_from(Uint256::from(balance).mul_floor(index - holder.index))?;
+    }
+    holder.index = index;
+    Ok(holder)
+}
+
+/// Checkpoints the total supply after a mint or burn, when snapshots are enabled
+pub fn checkpoint_total_supply(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    previous: Uint128,
+    total_supply: Uint128,
+) -> StdResult<()> {
+    if let Some(start) = SNAPSHOT_START.may_load(storage)? {
+        let last_checkpoint = SUPPLY_CHECKPOINTS
+            .keys(storage, None, None, Order::Descending)
+            .next()
+            .transpose()?;
+        if last_checkpoint.is_none_or(|height| height < start) {
+            SUPPLY_CHECKPOINTS.save(storage, start, &previous)?;
+        }
+        SUPPLY_CHECKPOINTS.save(storage, block.height, &total_supply)?;
+    }
+    Ok(())
+}
+
+/// Runs a cw20-base handler that updates `BALANCES` on its own, and replays the resulting
+/// balance changes of `accounts` through `on_balance_change`
+fn with_balance_hooks<F>(
+    mut deps: DepsMut,
+    env: Env,
+    accounts: Vec<Addr>,
+    handler: F,
+) -> Result<Response, ContractError>
+where
+    F: FnOnce(DepsMut, Env) -> Result<Response, ContractError>,
+{
+    let previous = accounts
+        .iter()
+        .map(|account| {
+            Ok(BALANCES
+                .may_load(deps.storage, account)?
+                .unwrap_or_default())
+        })
+        .collect::<StdResult<Vec<Uint128>>>()?;
+    let previous_supply = TOKEN_INFO.load(deps.storage)?.total_supply;
+
+    let res = handler(deps.branch(), env.clone())?;
+
+    for (account, previous) in accounts.iter().zip(previous) {
+        let balance = BALANCES
+            .may_load(deps.storage, account)?
+            .unwrap_or_default();
+        on_balance_change(deps.storage, &env.block, account, previous, balance)?;
+    }
+    let total_supply = TOKEN_INFO.load(deps.storage)?.total_supply;
+    if total_supply != previous_supply {
+        checkpoint_total_supply(deps.storage, &env.block, previous_supply, total_supply)?;
+    }
     Ok(res)
 }
 
+/// Credits the taxed amount to the treasury, records it in the lifetime tax statistics and
+/// the payer's tax ledger, and generates the `TransferEvent` log so explorers track the
+/// treasury balance properly
+fn collect_tax(
+    deps: &mut DepsMut,
+    env: &Env,
+    payer: &Addr,
+    taxes: &QueryTaxResponse,
+) -> Result<Vec<WasmMsg>, ContractError> {
+    let treasury = TREASURY.may_load(deps.storage)?.unwrap_or_default();
+
+    // In immediate burn mode the burn portion leaves the supply right away
+    let mut distributable = taxes.taxed_amount;
+    let burn_now = IMMEDIATE_BURN.may_load(deps.storage)?.unwrap_or(false);
+    if burn_now && !taxes.burn_amount.is_zero() {
+        let mut token_info = TOKEN_INFO.load(deps.storage)?;
+        let previous_supply = token_info.total_supply;
+        token_info.total_supply = previous_supply
+            .checked_sub(taxes.burn_amount)
+            .map_err(StdError::from)?;
+        TOKEN_INFO.save(deps.storage, &token_info)?;
+        checkpoint_total_supply(
+            deps.storage,
+            &env.block,
+            previous_supply,
+            token_info.total_supply,
+        )?;
+        distributable -= taxes.burn_amount;
+    }
+
+    // Extra destinations take their share first, the remainder goes to the treasury for liquify
+    let mut messages = vec![];
+    let mut to_treasury = distributable;
+    if !distributable.is_zero() {
+        for destination in TAX_DESTINATIONS.may_load(deps.storage)?.unwrap_or_default() {
+            let amount = distributable.mul_floor(destination.share);
+            if amount.is_zero() {
+                continue;
+            }
+            to_treasury -= amount;
+            increase_balance(deps.storage, &env.block, &destination.address, amount)?;
+            DESTINATION_RECEIVED.update(
+                deps.storage,
+                &destination.address,
+                |received| -> StdResult<_> { Ok(received.unwrap_or_default() + amount) },
+            )?;
+            messages.push(transfer_event_msg(
+                env,
+                payer,
+                destination.address.as_str(),
+                amount,
+            )?);
+        }
+    }
+
+    increase_balance(
+        deps.storage,
+        &env.block,
+        &deps.api.addr_validate(&treasury)?,
+        to_treasury,
+    )?;
+    messages.insert(0, transfer_event_msg(env, payer, &treasury, to_treasury)?);
+
+    // The treasury splits its balance as the taxes it received were split, whether the rates
+    // came from the policy or the built-in ones
+    if !to_treasury.is_zero() {
+        let mut split = TREASURY_SPLIT.may_load(deps.storage)?.unwrap_or_default();
+        split.total += to_treasury;
+        split.reflection += taxes
+            .reflection_amount
+            .multiply_ratio(to_treasury, distributable);
+        if !burn_now {
+            split.burn += taxes.burn_amount.multiply_ratio(to_treasury, distributable);
+        }
+        TREASURY_SPLIT.save(deps.storage, &split)?;
+    }
+
+    if !taxes.taxed_amount.is_zero() {
+        let mut stats = TAX_STATS.may_load(deps.storage)?.unwrap_or_default();
+        stats.total_taxed += taxes.taxed_amount;
+        stats.total_to_treasury += to_treasury;
+        stats.total_burned += taxes.burn_amount;
+        stats.total_reflected += taxes.reflection_amount;
+        stats.taxed_transfer_count += 1;
+        TAX_STATS.save(deps.storage, &stats)?;
+
+        HOLDER_TAX_PAID.update(deps.storage, payer, |paid| -> StdResult<_> {
+            Ok(paid.unwrap_or_default() + taxes.taxed_amount)
+        })?;
+    }
+
+    Ok(messages)
+}
+
+/// Self call logging a tax movement as a transfer, so explorers track it
+fn transfer_event_msg(env: &Env, from: &Addr, to: &str, amount: Uint128) -> StdResult<WasmMsg> {
+    Ok(WasmMsg::Execute {
+        contract_addr: env.contract.address.to_string(),
+        msg: to_json_binary(&ExecuteMsg::TransferEvent {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+        })?,
+        funds: vec![],
+    })
+}
+
+/// Replaces the extra tax destinations. Each receives its `share` of every taxed amount,
+/// the treasury keeps what is left.
+pub fn set_tax_destinations(
+    deps: DepsMut,
+    info: MessageInfo,
+    destinations: Vec<TaxDestinationMsg>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    if destinations.len() > MAX_TAX_DESTINATIONS {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "At most {} tax destinations are allowed",
+            MAX_TAX_DESTINATIONS
+        ))));
+    }
+
+    let treasury = TREASURY.may_load(deps.storage)?.unwrap_or_default();
+    let mut total_share = Decimal::zero();
+    let mut validated: Vec<TaxDestination> = vec![];
+    for destination in destinations {
+        let address = deps.api.addr_validate(&destination.address)?;
+        if address.as_str() == treasury || validated.iter().any(|d| d.address == address) {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Tax destinations must be unique and cannot include the treasury",
+            )));
+        }
+        total_share += destination.share;
+        validated.push(TaxDestination {
+            address,
+            share: destination.share,
+        });
+    }
+    if total_share > Decimal::one() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sum of tax destination shares must be <= 1",
+        )));
+    }
+
+    TAX_DESTINATIONS.save(deps.storage, &validated)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_tax_destinations")
+        .add_attribute("total_share", total_share.to_string()))
+}
+
+pub fn query_tax_destinations(storage: &dyn Storage) -> StdResult<TaxDestinationsResponse> {
+    let destinations = TAX_DESTINATIONS.may_load(storage)?.unwrap_or_default();
+    Ok(TaxDestinationsResponse {
+        destinations: destinations
+            .into_iter()
+            .map(|destination| {
+                Ok(TaxDestinationInfo {
+                    total_received: DESTINATION_RECEIVED
+                        .may_load(storage, &destination.address)?
+                        .unwrap_or_default(),
+                    address: destination.address.to_string(),
+                    share: destination.share,
+                })
+            })
+            .collect::<StdResult<_>>()?,
+    })
+}
+
 /// A simple, tax-free transfer used by trusted aggregators.
 pub fn execute_transfer_tax_exempt(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     recipient: String,
     amount: Uint128,
 ) -> Result<Response, ContractError> {
     let rcpt_addr = deps.api.addr_validate(&recipient)?;
 
-    BALANCES.update(
-        deps.storage,
-        &info.sender,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
-    BALANCES.update(
-        deps.storage,
-        &rcpt_addr,
-        |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
-    )?;
+    decrease_balance(deps.storage, &env.block, &info.sender, amount)?;
+    increase_balance(deps.storage, &env.block, &rcpt_addr, amount)?;
 
     Ok(Response::new()
         .add_attribute("action", "transfer")
@@ -649,6 +1161,7 @@ pub fn execute_transfer_tax_exempt(
 /// A simple, tax-free send used by trusted aggregators.
 pub fn execute_send_tax_exempt(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     contract: String,
     amount: Uint128,
@@ -656,18 +1169,8 @@ pub fn execute_send_tax_exempt(
 ) -> Result<Response, ContractError> {
     let rcpt_addr = deps.api.addr_validate(&contract)?;
 
-    BALANCES.update(
-        deps.storage,
-        &info.sender,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
-    BALANCES.update(
-        deps.storage,
-        &rcpt_addr,
-        |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
-    )?;
+    decrease_balance(deps.storage, &env.block, &info.sender, amount)?;
+    increase_balance(deps.storage, &env.block, &rcpt_addr, amount)?;
 
     Ok(Response::new()
         .add_attribute("action", "send")
@@ -743,11 +1246,82 @@ pub fn query_treasury(storage: &dyn Storage) -> Result<GetTreasuryResponse, StdE
     })
 }
 
+/// Returns the lifetime tax counters
+pub fn query_tax_stats(storage: &dyn Storage) -> Result<TaxStatsResponse, StdError> {
+    let stats = TAX_STATS.may_load(storage)?.unwrap_or_default();
+    Ok(TaxStatsResponse {
+        total_taxed: stats.total_taxed,
+        total_to_treasury: stats.total_to_treasury,
+        total_burned: stats.total_burned,
+        total_reflected: stats.total_reflected,
+        taxed_transfer_count: stats.taxed_transfer_count,
+    })
+}
+
 /// Used to calculate the amount of taxes to be paid, to be used in all transfer functions
-pub fn query_tax(storage: &dyn Storage, amount: Uint128) -> Result<QueryTaxResponse, StdError> {
-    let reflection_rate = REFLECTION_RATE.may_load(storage)?.unwrap();
-    let tax_rate = TAX_RATE.may_load(storage)?.unwrap();
-    let burn_rate = BURN_RATE.may_load(storage)?.unwrap();
+/// When a `sender` is given, its loyalty rebate is applied to the global tax rate
+pub fn query_tax(
+    deps: Deps,
+    env: &Env,
+    context: &TaxContext,
+    amount: Uint128,
+) -> Result<QueryTaxResponse, StdError> {
+    let TaxPolicyResponse {
+        mut tax_rate,
+        reflection_rate,
+        burn_rate,
+    } = query_tax_rates(deps, env, context, amount)?;
+
+    if let Some(sender) = &context.sender {
+        if let Some((_, rebate)) = query_rebate(deps.storage, env, sender)? {
+            tax_rate *= Decimal::one() - rebate;
+        }
+    }
+
+    // Sells pay a surcharge growing with their share of the pair's token reserve
+    let mut sell_pool_share = None;
+    let mut sell_surcharge = Decimal::zero();
+    let sold_pair = sold_into(deps.storage, context)?;
+    if let Some(pair) = &sold_pair {
+        let tiers = SELL_TAX_TIERS.may_load(deps.storage)?.unwrap_or_default();
+        if !tiers.is_empty() {
+            let reserve = query_token_reserve(deps, env, pair)?;
+            let share = Decimal::checked_from_ratio(amount, reserve).unwrap_or(Decimal::MAX);
+            sell_surcharge = tiers
+                .iter()
+                .filter(|tier| share >= tier.min_pool_share)
+                .map(|tier| tier.extra_rate)
+                .max()
+                .unwrap_or_default();
+            sell_pool_share = Some(share);
+            tax_rate = (tax_rate + sell_surcharge).min(Decimal::one());
+        }
+    }
+
+    // Selling back within the MEV guard window of a buy looks like a sandwich
+    let mut mev_detected = false;
+    let mut mev_surcharge = Decimal::zero();
+    if let (Some(guard), Some(_), Some(sender)) = (
+        MEV_GUARD.may_load(deps.storage)?,
+        &sold_pair,
+        &context.sender,
+    ) {
+        if let Some(bought) = LAST_BUY.may_load(deps.storage, sender)? {
+            mev_detected = env.block.height <= bought.saturating_add(guard.window_blocks);
+        }
+        if mev_detected {
+            mev_surcharge = guard.surcharge;
+            tax_rate = (tax_rate + mev_surcharge).min(Decimal::one());
+        }
+    }
+
+    let mut breaker_tripped = false;
+    if let (Some(breaker), Some(pair)) = (CIRCUIT_BREAKER.may_load(deps.storage)?, &sold_pair) {
+        breaker_tripped = current_sell_window(deps, env, &breaker, pair)?.exceeded_by(amount);
+        if let (true, Some(penalty_rate)) = (breaker_tripped, breaker.penalty_rate) {
+            tax_rate = (tax_rate + penalty_rate).min(Decimal::one());
+        }
+    }
 
     let taxed_amount = amount.mul_floor(tax_rate);
 
@@ -761,18 +1335,571 @@ pub fn query_tax(storage: &dyn Storage, amount: Uint128) -> Result<QueryTaxRespo
         after_tax,
         reflection_amount,
         liquidity_amount,
+        burn_amount,
+        tax_rate,
+        sell_pool_share,
+        sell_surcharge,
+        mev_detected,
+        mev_surcharge,
+        breaker_tripped,
     })
 }
 
-/// Returns the current tax rates
-pub fn query_rate(storage: &dyn Storage) -> Result<(Decimal, Decimal, Decimal, Decimal), StdError> {
-    let tax_rate = TAX_RATE.may_load(storage)?.unwrap();
-    let reflection_rate = REFLECTION_RATE.may_load(storage)?.unwrap();
-    let burn_rate = BURN_RATE.may_load(storage)?.unwrap();
-    let transfer_rate = MAX_TRANSFER_SUPPLY_RATE.may_load(storage)?.unwrap();
-
-    Ok((tax_rate, reflection_rate, burn_rate, transfer_rate))
-}
+/// Records buys from registered pairs for the MEV guard and sells into them for the circuit
+/// breaker, and rejects the sells either flags when configured to. Returns the outcomes to add
+/// to the transfer attributes.
+fn guard_trade(
+    deps: &mut DepsMut,
+    env: &Env,
+    context: &TaxContext,
+    amount: Uint128,
+    taxes: &QueryTaxResponse,
+    whitelisted: bool,
+) -> Result<Vec<Attribute>, ContractError> {
+    let mut attrs = vec![];
+
+    if let Some(guard) = MEV_GUARD.may_load(deps.storage)? {
+        if let (Some(sender), Some(recipient)) = (&context.sender, &context.recipient) {
+            if PAIRS.has(deps.storage, sender) && !PAIRS.has(deps.storage, recipient) {
+                LAST_BUY.save(deps.storage, recipient, &env.block.height)?;
+            }
+        }
+        if taxes.mev_detected && guard.reject && !whitelisted {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Sell too soon after a buy from a registered pair",
+            )));
+        }
+        attrs.push(attr("mev_detected", taxes.mev_detected.to_string()));
+    }
+
+    if let (Some(breaker), Some(pair)) = (
+        CIRCUIT_BREAKER.may_load(deps.storage)?,
+        sold_into(deps.storage, context)?,
+    ) {
+        if taxes.breaker_tripped && breaker.penalty_rate.is_none() && !whitelisted {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Sell volume circuit breaker tripped",
+            )));
+        }
+        // Whitelisted sells, such as the treasury's liquify, do not use up the users' window
+        if !whitelisted {
+            let mut window = current_sell_window(deps.as_ref(), env, &breaker, &pair)?;
+            window.volume += amount;
+            SELL_WINDOWS.save(deps.storage, &pair, &window)?;
+        }
+        attrs.push(attr(
+            "circuit_breaker_tripped",
+            taxes.breaker_tripped.to_string(),
+        ));
+    }
+
+    Ok(attrs)
+}
+
+/// Sold volume of the current window of `pair`. A window that has rolled is replaced by an
+/// empty one whose limit is measured from the pair's current token reserve.
+fn current_sell_window(
+    deps: Deps,
+    env: &Env,
+    breaker: &CircuitBreaker,
+    pair: &Addr,
+) -> StdResult<SellWindowState> {
+    let start = match breaker.window {
+        SellWindow::Block {} => env.block.height,
+        SellWindow::Seconds { seconds } => {
+            let now = env.block.time.seconds();
+            now - now % seconds
+        }
+    };
+    match SELL_WINDOWS.may_load(deps.storage, pair)? {
+        Some(window) if window.start == start => Ok(window),
+        _ => Ok(SellWindowState {
+            start,
+            volume: Uint128::zero(),
+            limit: query_token_reserve(deps, env, pair)?.mul_floor(breaker.max_pool_share),
+        }),
+    }
+}
+
+/// The registered pair receiving the tokens, when the movement is a sell
+pub fn sold_into(storage: &dyn Storage, context: &TaxContext) -> StdResult<Option<Addr>> {
+    Ok(context
+        .recipient
+        .clone()
+        .filter(|recipient| PAIRS.has(storage, recipient)))
+}
+
+/// Reserve of this token in a pair, from the pair's `Pool` query
+pub fn query_token_reserve(deps: Deps, env: &Env, pair: &Addr) -> StdResult<Uint128> {
+    let pool: PoolResponse = deps
+        .querier
+        .query_wasm_smart(pair, &PairQueryMsg::Pool {})?;
+    let token = AssetInfo::Token {
+        contract_addr: env.contract.address.to_string(),
+    };
+    pool.assets
+        .iter()
+        .find(|asset| asset.info.equal(&token))
+        .map(|asset| asset.amount)
+        .ok_or_else(|| StdError::generic_err("Pair does not trade this token"))
+}
+
+/// Registers a DEX pair of this token, transfers into it are treated as sells
+pub fn register_pair(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    registered: bool,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    let pair = deps.api.addr_validate(&address)?;
+    if registered {
+        PAIRS.save(deps.storage, &pair, &true)?;
+    } else {
+        PAIRS.remove(deps.storage, &pair);
+    }
+    Ok(Response::new()
+        .add_attribute("action", "register_pair")
+        .add_attribute("pair", address)
+        .add_attribute("registered", registered.to_string()))
+}
+
+/// Replaces the progressive sell tax tiers. A sell whose share of the pair's token reserve
+/// reaches `min_pool_share` pays `extra_rate` on top of the tax rate, the highest tier wins.
+pub fn set_sell_tax_tiers(
+    deps: DepsMut,
+    info: MessageInfo,
+    tiers: Vec<SellTaxTier>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    if tiers.iter().any(|tier| tier.extra_rate > Decimal::one()) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "extra_rate must be <= 1",
+        )));
+    }
+    SELL_TAX_TIERS.save(deps.storage, &tiers)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_sell_tax_tiers")
+        .add_attribute("tiers", tiers.len().to_string()))
+}
+
+/// Chooses whether the anti-whale limit is a share of the total supply (the `antiwhale_rate`
+/// of `SetTaxRate`) or a share of the token reserve of the pair a sell goes into
+pub fn set_antiwhale_mode(
+    deps: DepsMut,
+    info: MessageInfo,
+    mode: AntiwhaleMode,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    if let AntiwhaleMode::PoolReserve { max_pool_share } = &mode {
+        if *max_pool_share > Decimal::one() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "max_pool_share must be <= 1",
+            )));
+        }
+    }
+    ANTIWHALE_MODE.save(deps.storage, &mode)?;
+    Ok(Response::new().add_attribute("action", "set_antiwhale_mode"))
+}
+
+/// Configures the surcharge or rejection of sells made shortly after a buy from a pair
+pub fn set_mev_guard(
+    deps: DepsMut,
+    info: MessageInfo,
+    guard: Option<MevGuard>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    match &guard {
+        Some(guard) if guard.surcharge > Decimal::one() => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "surcharge must be <= 1",
+            )));
+        }
+        Some(guard) => MEV_GUARD.save(deps.storage, guard)?,
+        None => MEV_GUARD.remove(deps.storage),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_mev_guard")
+        .add_attribute("enabled", guard.is_some().to_string()))
+}
+
+pub fn set_circuit_breaker(
+    deps: DepsMut,
+    info: MessageInfo,
+    breaker: Option<CircuitBreaker>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    if let Some(breaker) = &breaker {
+        if breaker.max_pool_share > Decimal::one()
+            || breaker.penalty_rate.unwrap_or_default() > Decimal::one()
+        {
+            return Err(ContractError::Std(StdError::generic_err(
+                "max_pool_share and penalty_rate must be <= 1",
+            )));
+        }
+        if breaker.window == (SellWindow::Seconds { seconds: 0 }) {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Window must be at least one second",
+            )));
+        }
+        CIRCUIT_BREAKER.save(deps.storage, breaker)?;
+    } else {
+        CIRCUIT_BREAKER.remove(deps.storage);
+    }
+    // Windows measured under the previous settings no longer apply
+    SELL_WINDOWS.clear(deps.storage);
+    Ok(Response::new()
+        .add_attribute("action", "set_circuit_breaker")
+        .add_attribute("enabled", breaker.is_some().to_string()))
+}
+
+/// Admin override untripping the circuit breaker before its window rolls
+pub fn reset_circuit_breaker(
+    deps: DepsMut,
+    info: MessageInfo,
+    pair: Option<String>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    match &pair {
+        Some(pair) => {
+            let pair = deps.api.addr_validate(pair)?;
+            SELL_WINDOWS.remove(deps.storage, &pair);
+        }
+        None => SELL_WINDOWS.clear(deps.storage),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "reset_circuit_breaker")
+        .add_attribute("pair", pair.unwrap_or_else(|| "all".to_string())))
+}
+
+pub fn query_circuit_breaker(
+    deps: Deps,
+    env: &Env,
+    pair: String,
+) -> StdResult<CircuitBreakerResponse> {
+    let pair = deps.api.addr_validate(&pair)?;
+    let Some(breaker) = CIRCUIT_BREAKER.may_load(deps.storage)? else {
+        return Ok(CircuitBreakerResponse {
+            breaker: None,
+            window_start: 0,
+            volume: Uint128::zero(),
+            limit: Uint128::zero(),
+            tripped: false,
+        });
+    };
+    let window = current_sell_window(deps, env, &breaker, &pair)?;
+    Ok(CircuitBreakerResponse {
+        breaker: Some(breaker),
+        window_start: window.start,
+        volume: window.volume,
+        limit: window.limit,
+        tripped: window.tripped(),
+    })
+}
+
+pub fn query_pairs(storage: &dyn Storage) -> StdResult<PairsResponse> {
+    Ok(PairsResponse {
+        pairs: PAIRS
+            .keys(storage, None, None, Order::Ascending)
+            .map(|pair| pair.map(|pair| pair.to_string()))
+            .collect::<StdResult<_>>()?,
+        sell_tax_tiers: SELL_TAX_TIERS.may_load(storage)?.unwrap_or_default(),
+    })
+}
+
+/// Rates from the registered tax policy contract, or the built-in rates when none is set or
+/// the policy fails, answers with more than `MAX_POLICY_RESPONSE_BYTES` or returns out of
+/// bounds rates.
+///
+/// Queries carry no gas limit of their own, so the policy's gas is bounded by its code instead:
+/// it must come from a code id the admin allowed and cannot be migrated (see `set_tax_policy`).
+fn query_tax_rates(
+    deps: Deps,
+    env: &Env,
+    context: &TaxContext,
+    amount: Uint128,
+) -> StdResult<TaxPolicyResponse> {
+    if let Some(policy) = TAX_POLICY.may_load(deps.storage)? {
+        let request: QueryRequest = QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: policy.to_string(),
+            msg: to_json_binary(&TaxPolicyQueryMsg::TaxPolicy {
+                sender: context.sender.as_ref().map(Addr::to_string),
+                recipient: context.recipient.as_ref().map(Addr::to_string),
+                amount,
+                operation: context.operation,
+                block: env.block.clone(),
+            })?,
+        });
+        let rates = match deps.querier.raw_query(&to_json_vec(&request)?) {
+            SystemResult::Ok(result) => result
+                .into_result()
+                .ok()
+                .filter(|response| response.len() <= MAX_POLICY_RESPONSE_BYTES)
+                .and_then(|response| from_json::<TaxPolicyResponse>(&response).ok()),
+            SystemResult::Err(_) => None,
+        };
+        if let Some(rates) = rates {
+            if rates.tax_rate <= Decimal::one()
+                && rates.reflection_rate + rates.burn_rate <= Decimal::one()
+            {
+                return Ok(rates);
+            }
+        }
+    }
+
+    Ok(TaxPolicyResponse {
+        tax_rate: TAX_RATE.load(deps.storage)?,
+        reflection_rate: REFLECTION_RATE.load(deps.storage)?,
+        burn_rate: BURN_RATE.load(deps.storage)?,
+    })
+}
+
+/// Registers the contract queried for tax rates on every transfer, `None` restores the
+/// built-in rates. The contract must be instantiated from an allowed code id without a
+/// migration admin, so the code the admin reviewed is the code every transfer runs.
+pub fn set_tax_policy(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: Option<String>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    match &contract {
+        Some(contract) => {
+            let policy = deps.api.addr_validate(contract)?;
+            let contract_info = deps.querier.query_wasm_contract_info(policy.as_str())?;
+            let allowed = TAX_POLICY_CODES.may_load(deps.storage)?.unwrap_or_default();
+            if !allowed.contains(&contract_info.code_id) {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Tax policy code id is not allowed",
+                )));
+            }
+            if contract_info.admin.is_some() {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Tax policy contract must not be migratable",
+                )));
+            }
+            TAX_POLICY.save(deps.storage, &policy)?;
+        }
+        None => TAX_POLICY.remove(deps.storage),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_tax_policy")
+        .add_attribute("contract", contract.unwrap_or_default()))
+}
+
+/// Replaces the code ids tax policy contracts may be instantiated from. A registered policy
+/// whose code id is removed keeps working until it is replaced or unset.
+pub fn set_tax_policy_codes(
+    deps: DepsMut,
+    info: MessageInfo,
+    code_ids: Vec<u64>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    TAX_POLICY_CODES.save(deps.storage, &code_ids)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_tax_policy_codes")
+        .add_attribute(
+            "code_ids",
+            code_ids
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        ))
+}
+
+pub fn query_tax_policy_contract(storage: &dyn Storage) -> StdResult<GetTaxPolicyResponse> {
+    Ok(GetTaxPolicyResponse {
+        contract: TAX_POLICY
+            .may_load(storage)?
+            .map(|policy| policy.to_string()),
+        code_ids: TAX_POLICY_CODES.may_load(storage)?.unwrap_or_default(),
+    })
+}
+
+/// Returns the index and rebate of the best rebate tier the holder qualifies for
+pub fn query_rebate(
+    storage: &dyn Storage,
+    env: &Env,
+    holder: &Addr,
+) -> StdResult<Option<(u32, Decimal)>> {
+    let tiers = REBATE_TIERS.may_load(storage)?.unwrap_or_default();
+    if tiers.is_empty() {
+        return Ok(None);
+    }
+
+    let tax_paid = HOLDER_TAX_PAID
+        .may_load(storage, holder)?
+        .unwrap_or_default();
+    let held_days = HOLDER_SINCE
+        .may_load(storage, holder)?
+        .map(|since| env.block.time.seconds().saturating_sub(since) / SECONDS_PER_DAY);
+
+    let mut best: Option<(u32, Decimal)> = None;
+    for (index, tier) in tiers.iter().enumerate() {
+        let by_tax = tier.min_tax_paid.is_some_and(|min| tax_paid >= min);
+        let by_holding = tier
+            .min_holding_days
+            .is_some_and(|min| held_days.is_some_and(|days| days >= min));
+        if (by_tax || by_holding) && best.is_none_or(|(_, rebate)| tier.rebate > rebate) {
+            best = Some((index as u32, tier.rebate));
+        }
+    }
+    Ok(best)
+}
+
+/// Returns the loyalty tier and lifetime tax of a holder
+pub fn query_holder_tier(deps: Deps, env: &Env, address: String) -> StdResult<HolderTierResponse> {
+    let holder = deps.api.addr_validate(&address)?;
+    let rebate = query_rebate(deps.storage, env, &holder)?;
+    Ok(HolderTierResponse {
+        lifetime_tax_paid: HOLDER_TAX_PAID
+            .may_load(deps.storage, &holder)?
+            .unwrap_or_default(),
+        holding_since: HOLDER_SINCE.may_load(deps.storage, &holder)?,
+        tier: rebate.map(|(index, _)| index),
+        rebate: rebate.map(|(_, rebate)| rebate).unwrap_or_default(),
+        address,
+    })
+}
+
+/// Makes sure balances can be looked up at `height`, returning the height snapshots started at
+fn ensure_snapshot_height(storage: &dyn Storage, env: &Env, height: u64) -> StdResult<u64> {
+    let start = SNAPSHOT_START
+        .may_load(storage)?
+        .ok_or_else(|| StdError::generic_err("Snapshots are not enabled"))?;
+    if height < start {
+        return Err(StdError::generic_err(format!(
+            "Snapshots are only available from height {}",
+            start
+        )));
+    }
+    if height > env.block.height {
+        return Err(StdError::generic_err("Height is in the future"));
+    }
+    Ok(start)
+}
+
+/// Returns the balance of an address at the end of the block at `height`
+pub fn query_balance_at_height(
+    deps: Deps,
+    env: &Env,
+    address: String,
+    height: u64,
+) -> StdResult<BalanceResponse> {
+    let start = ensure_snapshot_height(deps.storage, env, height)?;
+    let address = deps.api.addr_validate(&address)?;
+
+    let checkpoint = BALANCE_CHECKPOINTS
+        .prefix(&address)
+        .range(
+            deps.storage,
+            None,
+            Some(Bound::inclusive(height)),
+            Order::Descending,
+        )
+        .next()
+        .transpose()?;
+    let balance = match checkpoint {
+        Some((checkpoint_height, balance)) if checkpoint_height >= start => balance,
+        // Not touched since snapshots started, so the balance has not changed since
+        _ => BALANCES
+            .may_load(deps.storage, &address)?
+            .unwrap_or_default(),
+    };
+    Ok(BalanceResponse { balance })
+}
+
+/// Returns the total supply at the end of the block at `height`
+pub fn query_total_supply_at_height(
+    deps: Deps,
+    env: &Env,
+    height: u64,
+) -> StdResult<TotalSupplyResponse> {
+    let start = ensure_snapshot_height(deps.storage, env, height)?;
+
+    let checkpoint = SUPPLY_CHECKPOINTS
+        .range(
+            deps.storage,
+            None,
+            Some(Bound::inclusive(height)),
+            Order::Descending,
+        )
+        .next()
+        .transpose()?;
+    let total_supply = match checkpoint {
+        Some((checkpoint_height, total_supply)) if checkpoint_height >= start => total_supply,
+        _ => TOKEN_INFO.load(deps.storage)?.total_supply,
+    };
+    Ok(TotalSupplyResponse { total_supply })
+}
+
+/// Returns the current tax rates
+pub fn query_rate(storage: &dyn Storage) -> Result<(Decimal, Decimal, Decimal, Decimal), StdError> {
+    let tax_rate = TAX_RATE.may_load(storage)?.unwrap();
+    let reflection_rate = REFLECTION_RATE.may_load(storage)?.unwrap();
+    let burn_rate = BURN_RATE.may_load(storage)?.unwrap();
+    let transfer_rate = MAX_TRANSFER_SUPPLY_RATE.may_load(storage)?.unwrap();
+
+    Ok((tax_rate, reflection_rate, burn_rate, transfer_rate))
+}
+
+/// Split the treasury applies to its balance on liquify: the split of the taxes it received
+/// since its last liquify, so the rates a tax policy returned carry over. Before any tax is
+/// received it follows the built-in rates. When the burn already happened at transfer time,
+/// the treasury burns nothing and the reflection share is scaled to what it holds.
+pub fn query_treasury_rates(storage: &dyn Storage) -> StdResult<TreasuryRatesResponse> {
+    let split = TREASURY_SPLIT.may_load(storage)?.unwrap_or_default();
+    if !split.total.is_zero() {
+        return Ok(TreasuryRatesResponse {
+            reflection_rate: Decimal::from_ratio(split.reflection, split.total),
+            burn_rate: Decimal::from_ratio(split.burn, split.total),
+        });
+    }
+
+    let reflection_rate = REFLECTION_RATE.load(storage)?;
+    let burn_rate = BURN_RATE.load(storage)?;
+    if !IMMEDIATE_BURN.may_load(storage)?.unwrap_or(false) {
+        return Ok(TreasuryRatesResponse {
+            reflection_rate,
+            burn_rate,
+        });
+    }
+
+    let remaining = Decimal::one() - burn_rate;
+    let reflection_rate = if remaining.is_zero() {
+        Decimal::zero()
+    } else {
+        (reflection_rate / remaining).min(Decimal::one())
+    };
+    Ok(TreasuryRatesResponse {
+        reflection_rate,
+        burn_rate: Decimal::zero(),
+    })
+}
+
+/// Only callable by the treasury. Starts a new treasury split once its balance was liquified.
+pub fn treasury_liquified(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let treasury = TREASURY.may_load(deps.storage)?.unwrap_or_default();
+    if info.sender.as_str() != treasury {
+        return Err(ContractError::Unauthorized {});
+    }
+    TREASURY_SPLIT.remove(deps.storage);
+    Ok(Response::new().add_attribute("action", "treasury_liquified"))
+}
+
+/// Burns the burn portion of the tax during the transfer instead of in the treasury
+pub fn set_immediate_burn(
+    deps: DepsMut,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    IMMEDIATE_BURN.save(deps.storage, &enabled)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_immediate_burn")
+        .add_attribute("enabled", enabled.to_string()))
+}
 
 pub fn query_whitelist(storage: &dyn Storage, address: String) -> Result<bool, StdError> {
     let whitelist = WHITELIST.may_load(storage, address)?.unwrap();
@@ -820,6 +1947,433 @@ pub fn set_tax_rate(
     Ok(Response::default())
 }
 
+/// Replaces the loyalty rebate tiers. A tier applies once the holder has paid at least
+/// `min_tax_paid` in taxes or held tokens for at least `min_holding_days`, and reduces the
+/// global tax rate by the `rebate` fraction. The best qualifying tier wins.
+pub fn set_rebate_tiers(
+    deps: DepsMut,
+    info: MessageInfo,
+    tiers: Vec<RebateTier>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+
+    for tier in tiers.iter() {
+        if tier.rebate > Decimal::one() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "rebate must be <= 1",
+            )));
+        }
+        if tier.min_tax_paid.is_none() && tier.min_holding_days.is_none() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "rebate tier requires min_tax_paid or min_holding_days",
+            )));
+        }
+    }
+
+    REBATE_TIERS.save(deps.storage, &tiers)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_rebate_tiers")
+        .add_attribute("tiers", tiers.len().to_string()))
+}
+
+/// Enables or disables balance and total supply snapshots. History is only available
+/// from the height snapshots were last enabled at.
+pub fn set_snapshots(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+
+    if enabled {
+        if !SNAPSHOT_START.exists(deps.storage) {
+            SNAPSHOT_START.save(deps.storage, &env.block.height)?;
+        }
+    } else {
+        SNAPSHOT_START.remove(deps.storage);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_snapshots")
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+/// Called by the treasury when reward tokens arrive, spreads `amount` over all eligible holders
+pub fn distribute_dividends(
+    deps: DepsMut,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let treasury = TREASURY.may_load(deps.storage)?.unwrap_or_default();
+    if info.sender.as_str() != treasury {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let supply = DIVIDEND_SUPPLY.may_load(deps.storage)?.unwrap_or_default();
+    let undistributed = UNDISTRIBUTED_DIVIDENDS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let total = amount + undistributed;
+
+    if supply.is_zero() {
+        UNDISTRIBUTED_DIVIDENDS.save(deps.storage, &total)?;
+        return Ok(Response::new()
+            .add_attribute("action", "distribute_dividends")
+            .add_attribute("undistributed", total));
+    }
+
+    let increase = Decimal256::checked_from_ratio(total, supply)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+    let index = DIVIDEND_INDEX.may_load(deps.storage)?.unwrap_or_default();
+    DIVIDEND_INDEX.save(
+        deps.storage,
+        &index.checked_add(increase).map_err(StdError::from)?,
+    )?;
+    // The index is rounded down, what it does not account for waits for the next distribution
+    let distributed =
+        Uint128::try_from(Uint256::from(supply).mul_floor(increase)).map_err(StdError::from)?;
+    UNDISTRIBUTED_DIVIDENDS.save(deps.storage, &(total - distributed))?;
+
+    Ok(Response::new()
+        .add_attribute("action", "distribute_dividends")
+        .add_attribute("amount", distributed)
+        .add_attribute("undistributed", total - distributed))
+}
+
+/// Pays out the dividends accrued by the sender through the treasury
+pub fn claim_dividends(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let balance = BALANCES
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    let balance = if DIVIDEND_EXCLUDED.has(deps.storage, &info.sender) {
+        Uint128::zero()
+    } else {
+        balance
+    };
+    let mut holder = settle_dividends(deps.storage, &info.sender, balance)?;
+    let amount = holder.pending;
+    if amount.is_zero() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "No dividends to claim",
+        )));
+    }
+    holder.pending = Uint128::zero();
+    HOLDER_DIVIDENDS.save(deps.storage, &info.sender, &holder)?;
+
+    let treasury = TREASURY.load(deps.storage)?;
+    Ok(Response::new()
+        .add_message(WasmMsg::Execute {
+            contract_addr: treasury,
+            msg: to_json_binary(&TreasuryExecuteMsg::PayDividend {
+                recipient: info.sender.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        })
+        .add_attribute("action", "claim")
+        .add_attribute("recipient", info.sender)
+        .add_attribute("amount", amount))
+}
+
+/// Excludes an address (e.g. a liquidity pair) from earning dividends, or includes it again
+pub fn set_dividend_exclusion(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    excluded: bool,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    let addr = deps.api.addr_validate(&address)?;
+    exclude_from_dividends(deps.storage, &addr, excluded)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_dividend_exclusion")
+        .add_attribute("address", address)
+        .add_attribute("excluded", excluded.to_string()))
+}
+
+fn exclude_from_dividends(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    excluded: bool,
+) -> StdResult<()> {
+    if DIVIDEND_EXCLUDED.has(storage, address) == excluded {
+        return Ok(());
+    }
+
+    let balance = BALANCES.may_load(storage, address)?.unwrap_or_default();
+    let supply = DIVIDEND_SUPPLY.may_load(storage)?.unwrap_or_default();
+    if excluded {
+        // Keep what was earned so far, but stop earning on the balance
+        let holder = settle_dividends(storage, address, balance)?;
+        HOLDER_DIVIDENDS.save(storage, address, &holder)?;
+        DIVIDEND_SUPPLY.save(storage, &supply.saturating_sub(balance))?;
+        DIVIDEND_EXCLUDED.save(storage, address, &true)?;
+    } else {
+        let holder = settle_dividends(storage, address, Uint128::zero())?;
+        HOLDER_DIVIDENDS.save(storage, address, &holder)?;
+        DIVIDEND_SUPPLY.save(storage, &(supply + balance))?;
+        DIVIDEND_EXCLUDED.remove(storage, address);
+    }
+    Ok(())
+}
+
+pub fn query_dividends(deps: Deps, address: String) -> StdResult<DividendsResponse> {
+    let addr = deps.api.addr_validate(&address)?;
+    let excluded = DIVIDEND_EXCLUDED.has(deps.storage, &addr);
+    let balance = if excluded {
+        Uint128::zero()
+    } else {
+        BALANCES.may_load(deps.storage, &addr)?.unwrap_or_default()
+    };
+    let holder = settle_dividends(deps.storage, &addr, balance)?;
+    Ok(DividendsResponse {
+        address,
+        claimable: holder.pending,
+        excluded,
+    })
+}
+
+pub fn register_permit_key(
+    deps: DepsMut,
+    info: MessageInfo,
+    public_key: Binary,
+) -> Result<Response, ContractError> {
+    VerificationKey::try_from(public_key.as_slice())
+        .map_err(|_| StdError::generic_err("Invalid ed25519 public key"))?;
+    PERMIT_KEYS.save(deps.storage, &info.sender, &public_key)?;
+    Ok(Response::new()
+        .add_attribute("action", "register_permit_key")
+        .add_attribute("owner", info.sender))
+}
+
+/// Checks `signature` over the JSON of `payload` against the owner's registered key and
+/// consumes the nonce
+fn verify_signed<T: Serialize>(
+    deps: &mut DepsMut,
+    owner: &Addr,
+    nonce: u64,
+    payload: &T,
+    signature: &Binary,
+) -> Result<(), ContractError> {
+    let public_key = PERMIT_KEYS
+        .may_load(deps.storage, owner)?
+        .ok_or_else(|| StdError::generic_err("No permit key registered for owner"))?;
+
+    let expected_nonce = NONCES.may_load(deps.storage, owner)?.unwrap_or_default();
+    if nonce != expected_nonce {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Invalid nonce, expected {}",
+            expected_nonce
+        ))));
+    }
+
+    let key = VerificationKey::try_from(public_key.as_slice())
+        .map_err(|_| StdError::generic_err("Invalid ed25519 public key"))?;
+    let signature = Signature::from_slice(signature.as_slice())
+        .map_err(|_| StdError::generic_err("Invalid ed25519 signature"))?;
+    key.verify(&signature, &to_json_vec(payload)?)
+        .map_err(|_| StdError::generic_err("Signature verification failed"))?;
+
+    NONCES.save(deps.storage, owner, &(expected_nonce + 1))?;
+    Ok(())
+}
+
+/// Sets an allowance from a permit signed by the owner
+pub fn permit(
+    mut deps: DepsMut,
+    env: Env,
+    payload: PermitPayload,
+    signature: Binary,
+) -> Result<Response, ContractError> {
+    let owner_addr = deps.api.addr_validate(&payload.owner)?;
+    let spender_addr = deps.api.addr_validate(&payload.spender)?;
+    if owner_addr == spender_addr {
+        return Err(ContractError::CannotSetOwnAccount {});
+    }
+
+    let expires = payload.expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::InvalidExpiration {});
+    }
+
+    verify_signed(&mut deps, &owner_addr, payload.nonce, &payload, &signature)?;
+
+    if payload.amount.is_zero() {
+        ALLOWANCES.remove(deps.storage, (&owner_addr, &spender_addr));
+        ALLOWANCES_SPENDER.remove(deps.storage, (&spender_addr, &owner_addr));
+    } else {
+        let allowance = AllowanceResponse {
+            allowance: payload.amount,
+            expires,
+        };
+        ALLOWANCES.save(deps.storage, (&owner_addr, &spender_addr), &allowance)?;
+        ALLOWANCES_SPENDER.save(deps.storage, (&spender_addr, &owner_addr), &allowance)?;
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "permit"),
+        attr("owner", payload.owner),
+        attr("spender", payload.spender),
+        attr("amount", payload.amount),
+        attr("nonce", payload.nonce.to_string()),
+    ]))
+}
+
+/// Executes a transfer or send signed by the owner, paying the relayer its fee
+pub fn relay_transfer(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payload: RelayPayload,
+    signature: Binary,
+) -> Result<Response, ContractError> {
+    if env.block.time.seconds() > payload.deadline {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Relayed transfer deadline has passed",
+        )));
+    }
+
+    // A payload bound to a relayer cannot be front-run for its fee
+    if let Some(relayer) = &payload.relayer {
+        if info.sender.as_str() != relayer {
+            return Err(ContractError::Unauthorized {});
+        }
+    }
+
+    let owner_addr = deps.api.addr_validate(&payload.owner)?;
+    verify_signed(&mut deps, &owner_addr, payload.nonce, &payload, &signature)?;
+
+    let owner_info = MessageInfo {
+        sender: owner_addr,
+        funds: vec![],
+    };
+    let mut response = match payload.action {
+        RelayAction::Transfer { recipient, amount } => execute_transfer(
+            deps.branch(),
+            env.clone(),
+            owner_info.clone(),
+            recipient,
+            amount,
+        )?,
+        RelayAction::Send {
+            contract,
+            amount,
+            msg,
+        } => execute_send(
+            deps.branch(),
+            env.clone(),
+            owner_info.clone(),
+            contract,
+            amount,
+            msg,
+        )?,
+    };
+
+    if let Some(fee) = payload.fee.filter(|fee| !fee.is_zero()) {
+        let fee_response = execute_transfer(deps, env, owner_info, info.sender.to_string(), fee)?;
+        response = response
+            .add_submessages(fee_response.messages)
+            .add_attributes(fee_response.attributes)
+            .add_events(fee_response.events)
+            .add_attribute("relayer_fee", fee);
+    }
+
+    Ok(response
+        .add_attribute("relayer", info.sender)
+        .add_attribute("nonce", payload.nonce.to_string()))
+}
+
+fn add_vesting(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    schedule: &VestingSchedule,
+) -> Result<(), ContractError> {
+    if schedule.amount.is_zero()
+        || schedule.end_time <= schedule.start_time
+        || schedule.cliff_time < schedule.start_time
+        || schedule.cliff_time > schedule.end_time
+    {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Invalid vesting schedule",
+        )));
+    }
+
+    let mut schedules = VESTING.may_load(storage, address)?.unwrap_or_default();
+    schedules.push(Vesting {
+        amount: schedule.amount,
+        start_time: schedule.start_time,
+        cliff_time: schedule.cliff_time,
+        end_time: schedule.end_time,
+    });
+    VESTING.save(storage, address, &schedules)?;
+    Ok(())
+}
+
+/// Amount of `address`'s balance still locked at block time `now`
+pub fn locked_amount(storage: &dyn Storage, address: &Addr, now: u64) -> StdResult<Uint128> {
+    let schedules = VESTING.may_load(storage, address)?.unwrap_or_default();
+    Ok(schedules
+        .iter()
+        .map(|vesting| vesting.amount - vesting.vested(now))
+        .sum())
+}
+
+/// Sends tokens from the admin's balance into a new vesting schedule
+pub fn create_vesting(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    schedule: VestingSchedule,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    let beneficiary = deps.api.addr_validate(&schedule.address)?;
+
+    decrease_balance(deps.storage, &env.block, &info.sender, schedule.amount)?;
+    increase_balance(deps.storage, &env.block, &beneficiary, schedule.amount)?;
+    add_vesting(deps.storage, &beneficiary, &schedule)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "create_vesting")
+        .add_attribute("address", beneficiary)
+        .add_attribute("amount", schedule.amount)
+        .add_attribute("cliff_time", schedule.cliff_time.to_string())
+        .add_attribute("end_time", schedule.end_time.to_string()))
+}
+
+pub fn query_vesting(deps: Deps, env: Env, address: String) -> StdResult<VestingResponse> {
+    let addr = deps.api.addr_validate(&address)?;
+    let now = env.block.time.seconds();
+    let schedules = VESTING.may_load(deps.storage, &addr)?.unwrap_or_default();
+    let vested = schedules.iter().map(|vesting| vesting.vested(now)).sum();
+    let locked = locked_amount(deps.storage, &addr, now)?;
+    Ok(VestingResponse {
+        schedules: schedules
+            .into_iter()
+            .map(|vesting| VestingSchedule {
+                address: address.clone(),
+                amount: vesting.amount,
+                start_time: vesting.start_time,
+                cliff_time: vesting.cliff_time,
+                end_time: vesting.end_time,
+            })
+            .collect(),
+        address,
+        vested,
+        locked,
+    })
+}
+
+pub fn query_permit_nonce(deps: Deps, owner: String) -> StdResult<PermitNonceResponse> {
+    let owner_addr = deps.api.addr_validate(&owner)?;
+    Ok(PermitNonceResponse {
+        public_key: PERMIT_KEYS.may_load(deps.storage, &owner_addr)?,
+        nonce: NONCES
+            .may_load(deps.storage, &owner_addr)?
+            .unwrap_or_default(),
+    })
+}
+
 /// Sets which addresses are whitelisted (not taxed)
 pub fn set_whitelist(
     deps: DepsMut,
@@ -848,17 +2402,34 @@ pub fn ensure_admin(deps: &DepsMut, info: &MessageInfo) -> Result<Response, Cont
 /// This is used to prevent whales from moving more than 2% of supply at once
 pub fn ensure_antiwhale(
     deps: &DepsMut,
+    env: &Env,
     from: String,
+    recipient: &str,
     transfer_balance: Uint128,
 ) -> Result<Response, ContractError> {
-    let token_info = TOKEN_INFO.may_load(deps.storage)?.unwrap();
-    let transfer_rate = MAX_TRANSFER_SUPPLY_RATE.may_load(deps.storage)?.unwrap();
     let whitelist = WHITELIST
         .may_load(deps.storage, from.clone())?
         .unwrap_or(false);
 
     // Calculate the exact anti-whale limit in token units
-    let limit = token_info.total_supply.mul_floor(transfer_rate);
+    let mode = ANTIWHALE_MODE
+        .may_load(deps.storage)?
+        .unwrap_or(AntiwhaleMode::TotalSupply {});
+    let limit = match mode {
+        AntiwhaleMode::TotalSupply {} => {
+            let token_info = TOKEN_INFO.may_load(deps.storage)?.unwrap();
+            let transfer_rate = MAX_TRANSFER_SUPPLY_RATE.may_load(deps.storage)?.unwrap();
+            token_info.total_supply.mul_floor(transfer_rate)
+        }
+        // Only sells are limited, by the depth of the pair they go into
+        AntiwhaleMode::PoolReserve { max_pool_share } => {
+            let recipient = deps.api.addr_validate(recipient)?;
+            if !PAIRS.has(deps.storage, &recipient) {
+                return Ok(Response::default());
+            }
+            query_token_reserve(deps.as_ref(), env, &recipient)?.mul_floor(max_pool_share)
+        }
+    };
 
     // Whitelisted contracts can bypass antiwhale, inclusive of treasury contract
     if transfer_balance >= limit && !whitelist {
@@ -914,13 +2485,21 @@ pub fn generate_transfer_event(
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Tokens deployed before dividends existed start with every balance except the treasury eligible
+    if !DIVIDEND_SUPPLY.exists(deps.storage) {
+        let total_supply = TOKEN_INFO.load(deps.storage)?.total_supply;
+        DIVIDEND_SUPPLY.save(deps.storage, &total_supply)?;
+        if let Some(treasury) = TREASURY.may_load(deps.storage)? {
+            exclude_from_dividends(deps.storage, &Addr::unchecked(treasury), true)?;
+        }
+    }
     Ok(Response::default())
 }
 
 pub fn migrate_treasury(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     code_id: u64,
 ) -> Result<Response, ContractError> {
@@ -930,6 +2509,21 @@ pub fn migrate_treasury(
         return Err(ContractError::Std(StdError::generic_err("Not admin")));
     }
 
+    // A new treasury code could drop the LP lock, so it has to be fully released first.
+    // Treasuries from before the lock do not answer the query and hold no locked LP.
+    let lock: Option<TreasuryLpLockResponse> = deps
+        .querier
+        .query_wasm_smart(&treasury, &TreasuryQueryMsg::LpLock {})
+        .ok();
+    if let Some(release_end) = lock.and_then(|lock| lock.release_end) {
+        if release_end > env.block.time.seconds() {
+            return Err(ContractError::Std(StdError::generic_err(format!(
+                "Treasury LP is locked until {}",
+                release_end
+            ))));
+        }
+    }
+
     Ok(
         Response::new().add_message(CosmosMsg::Wasm(WasmMsg::Migrate {
             contract_addr: treasury,
@@ -975,6 +2569,7 @@ pub fn register_deployment(
 
     TREASURY.save(deps.storage, &contract_addr.to_string())?;
     WHITELIST.save(deps.storage, contract_addr.to_string(), &true)?;
+    exclude_from_dividends(deps.storage, &contract_addr, true)?;
 
     Ok(Response::new())
 }
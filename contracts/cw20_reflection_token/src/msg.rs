@@ -1,4 +1,4 @@
-use cosmwasm_std::{Addr, Binary};
+use cosmwasm_std::{Addr, Binary, BlockInfo};
 use cosmwasm_std::{Decimal, StdError, StdResult, Uint128};
 use cw20::{Cw20Coin, Expiration, Logo, MinterResponse};
 use schemars::JsonSchema;
@@ -23,6 +23,21 @@ pub struct InstantiateMsg {
     pub router: String,
     pub mint: Option<MinterResponse>,
     pub marketing: Option<InstantiateMarketingInfo>,
+    /// Checkpoints balances and total supply for historical queries
+    pub enable_snapshots: Option<bool>,
+    /// Locks part of `initial_balances` under vesting schedules
+    pub vesting: Option<Vec<VestingSchedule>>,
+}
+
+/// Locks `amount` of `address`'s balance. Nothing vests before `cliff_time`, after which the
+/// amount vests linearly from `start_time` until fully vested at `end_time` (block times in seconds).
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct VestingSchedule {
+    pub address: String,
+    pub amount: Uint128,
+    pub start_time: u64,
+    pub cliff_time: u64,
+    pub end_time: u64,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
@@ -121,6 +136,8 @@ pub enum ExecuteMsg {
         to: String,
         amount: Uint128,
     },
+    /// Only callable by the admin. Migrates the treasury to `code_id`, refused until the
+    /// treasury's LP lock has fully released so a new code cannot drop the lock.
     MigrateTreasury {
         code_id: u64,
     },
@@ -158,6 +175,253 @@ pub enum ExecuteMsg {
     TransferAdmin {
         new_admin: String,
     },
+    /// Replaces the loyalty rebate tiers applied to the tax of long-term holders.
+    SetRebateTiers {
+        tiers: Vec<RebateTier>,
+    },
+    /// Enables or disables checkpointing of balances and total supply.
+    SetSnapshots {
+        enabled: bool,
+    },
+    /// Only callable by the treasury. Spreads `amount` of reward token over all eligible holders.
+    DistributeDividends {
+        amount: Uint128,
+    },
+    /// Pays the reward token dividends accrued by the sender out of the treasury.
+    Claim {},
+    /// Excludes an address (e.g. a liquidity pair) from earning dividends, or includes it again.
+    SetDividendExclusion {
+        address: String,
+        excluded: bool,
+    },
+    /// Registers (or rotates) the sender's ed25519 public key used to sign permits.
+    RegisterPermitKey {
+        public_key: Binary,
+    },
+    /// Sets `spender`'s allowance over `owner`'s tokens from a `PermitPayload` signed by the
+    /// owner's registered key. Can be submitted by anyone.
+    Permit {
+        owner: String,
+        spender: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+        nonce: u64,
+        signature: Binary,
+    },
+    /// Executes a transfer or send signed by `owner` as if the owner sent it, paying the
+    /// submitting relayer an optional `fee` from the owner's balance. The signature covers
+    /// a `RelayPayload`; `deadline` is a block time in seconds. When `relayer` is set, only
+    /// that address can submit it.
+    RelayTransfer {
+        owner: String,
+        action: RelayAction,
+        fee: Option<Uint128>,
+        #[serde(default)]
+        relayer: Option<String>,
+        nonce: u64,
+        deadline: u64,
+        signature: Binary,
+    },
+    /// Only callable by the admin. Moves `schedule.amount` from the admin's balance to
+    /// `schedule.address`, locked under the schedule.
+    CreateVesting {
+        schedule: VestingSchedule,
+    },
+    /// Only callable by the admin. Registers a contract implementing `TaxPolicyQueryMsg` that
+    /// decides the tax rate and split of every transfer. `None` restores the built-in rates.
+    /// The policy query cannot be gas limited, so the allow-list and the missing migration
+    /// admin are the only bound: a policy that runs out of gas fails every taxed transfer, and
+    /// whoever can configure the policy, such as its own admin, sets the rates transfers pay.
+    SetTaxPolicy {
+        contract: Option<String>,
+    },
+    /// Only callable by the admin. Replaces the code ids a tax policy contract may be
+    /// instantiated from.
+    SetTaxPolicyCodes {
+        code_ids: Vec<u64>,
+    },
+    /// Only callable by the treasury after a liquify. Resets the split reported by
+    /// `TreasuryRates`.
+    TreasuryLiquified {},
+    /// Only callable by the admin. Replaces the extra destinations (e.g. marketing or dev
+    /// wallets) credited with their share of every taxed amount. The treasury receives the rest.
+    SetTaxDestinations {
+        destinations: Vec<TaxDestinationMsg>,
+    },
+    /// Only callable by the admin. Removes the burn portion of the tax from the total supply
+    /// during the transfer instead of sending it to the treasury to burn on liquify.
+    SetImmediateBurn {
+        enabled: bool,
+    },
+    /// Only callable by the admin. Registers a DEX pair of this token; transfers into it are
+    /// treated as sells.
+    RegisterPair {
+        address: String,
+    },
+    DeregisterPair {
+        address: String,
+    },
+    /// Only callable by the admin. Replaces the progressive sell tax tiers.
+    SetSellTaxTiers {
+        tiers: Vec<SellTaxTier>,
+    },
+    /// Only callable by the admin. Chooses how the anti-whale limit is computed.
+    SetAntiwhaleMode {
+        mode: AntiwhaleMode,
+    },
+    /// Only callable by the admin. Configures the surcharge on addresses selling back into a
+    /// registered pair shortly after buying from one. `None` disables it.
+    SetMevGuard {
+        guard: Option<MevGuard>,
+    },
+    /// Only callable by the admin. Configures the circuit breaker on the volume sold into each
+    /// registered pair. `None` disables it.
+    SetCircuitBreaker {
+        breaker: Option<CircuitBreaker>,
+    },
+    /// Only callable by the admin. Clears the sold volume of the current window of `pair`, or
+    /// of every pair when not given, untripping the circuit breaker.
+    ResetCircuitBreaker {
+        pair: Option<String>,
+    },
+}
+
+/// Limits the volume sold into a registered pair per window to `max_pool_share` of its token
+/// reserve, measured when the window opens. Once a window's volume exceeds the limit, further
+/// sells into the pair pay `penalty_rate` on top of the tax rate, or are rejected when not set.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct CircuitBreaker {
+    pub window: SellWindow,
+    pub max_pool_share: Decimal,
+    pub penalty_rate: Option<Decimal>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SellWindow {
+    /// A new window every block
+    Block {},
+    /// A new window every `seconds` of block time
+    Seconds { seconds: u64 },
+}
+
+/// Sells made by an address within `window_blocks` blocks of its last buy from a registered
+/// pair (0 means the same block) pay `surcharge` on top of the tax rate, or are rejected
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct MevGuard {
+    pub window_blocks: u64,
+    pub surcharge: Decimal,
+    pub reject: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AntiwhaleMode {
+    /// Any transfer is limited to `antiwhale_rate` of the total supply
+    TotalSupply {},
+    /// Sells into a registered pair are limited to `max_pool_share` of its token reserve,
+    /// other transfers are not limited
+    PoolReserve { max_pool_share: Decimal },
+}
+
+/// Sells whose share of the pair's token reserve reaches `min_pool_share` pay `extra_rate`
+/// on top of the tax rate
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct SellTaxTier {
+    pub min_pool_share: Decimal,
+    pub extra_rate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct TaxDestinationMsg {
+    pub address: String,
+    /// Fraction of the taxed amount, shares of all destinations must add up to at most 1
+    pub share: Decimal,
+}
+
+/// Kind of token movement being taxed
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxOperation {
+    Transfer,
+    Send,
+    TransferFrom,
+    SendFrom,
+}
+
+/// Query the token sends to its tax policy contract
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxPolicyQueryMsg {
+    TaxPolicy {
+        sender: Option<String>,
+        recipient: Option<String>,
+        amount: Uint128,
+        operation: TaxOperation,
+        block: BlockInfo,
+    },
+}
+
+/// Rates returned by the tax policy contract, bounded like `SetTaxRate`
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TaxPolicyResponse {
+    pub tax_rate: Decimal,
+    pub reflection_rate: Decimal,
+    pub burn_rate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayAction {
+    Transfer {
+        recipient: String,
+        amount: Uint128,
+    },
+    Send {
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    },
+}
+
+/// Document signed for `ExecuteMsg::RelayTransfer`, serialized as JSON.
+/// Shares the nonce sequence of `PermitPayload`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RelayPayload {
+    pub chain_id: String,
+    pub contract: String,
+    pub owner: String,
+    pub action: RelayAction,
+    pub fee: Option<Uint128>,
+    /// Only address allowed to submit the transfer and collect the fee. Left out of the
+    /// signed JSON when not set, so payloads signed without it stay valid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relayer: Option<String>,
+    pub nonce: u64,
+    pub deadline: u64,
+}
+
+/// Document signed for `ExecuteMsg::Permit`, serialized as JSON.
+/// `chain_id` and `contract` bind the signature to this token deployment.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PermitPayload {
+    pub chain_id: String,
+    pub contract: String,
+    pub owner: String,
+    pub spender: String,
+    pub amount: Uint128,
+    pub expires: Option<Expiration>,
+    pub nonce: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RebateTier {
+    /// Lifetime tax the holder must have paid to qualify
+    pub min_tax_paid: Option<Uint128>,
+    /// Days the holder must have held tokens to qualify
+    pub min_holding_days: Option<u64>,
+    /// Fraction of the global tax rate waived for qualifying holders
+    pub rebate: Decimal,
 }
 
 impl InstantiateMsg {
@@ -252,14 +516,93 @@ pub enum QueryMsg {
     /// contract.
     /// Return type: DownloadLogoResponse.
     DownloadLogo {},
+    /// Simulates the tax on a transfer of `amount`. When `sender` is given,
+    /// its loyalty rebate is applied. `recipient` and `operation` (default `transfer`)
+    /// are passed on to the tax policy contract.
+    /// Return type: QueryTaxResponse.
     QueryTax {
         amount: Uint128,
+        sender: Option<String>,
+        recipient: Option<String>,
+        operation: Option<TaxOperation>,
     },
     QueryRates {},
+    /// Returns the split the treasury applies to its balance on liquify.
+    /// Return type: TreasuryRatesResponse.
+    TreasuryRates {},
     GetWhitelist {
         address: String,
     },
     GetTreasury {},
+    /// Return type: GetTaxPolicyResponse.
+    GetTaxPolicy {},
+    /// Returns the registered pairs and the progressive sell tax tiers.
+    /// Return type: PairsResponse.
+    Pairs {},
+    /// Return type: AntiwhaleMode.
+    AntiwhaleMode {},
+    /// Return type: Option<MevGuard>.
+    MevGuard {},
+    /// Returns the circuit breaker and the sold volume of the current window of `pair`.
+    /// Return type: CircuitBreakerResponse.
+    CircuitBreaker {
+        pair: String,
+    },
+    /// Returns the extra tax destinations and what each has received.
+    /// Return type: TaxDestinationsResponse.
+    TaxDestinations {},
+    /// Returns the lifetime tax counters of the token.
+    /// Return type: TaxStatsResponse.
+    TaxStats {},
+    /// Returns the loyalty tier and lifetime tax paid of a holder.
+    /// Return type: HolderTierResponse.
+    HolderTier {
+        address: String,
+    },
+    /// Only with snapshots enabled.
+    /// Returns the balance of the given address at the end of the block at `height`.
+    /// Return type: BalanceResponse.
+    BalanceAtHeight {
+        address: String,
+        height: u64,
+    },
+    /// Only with snapshots enabled.
+    /// Returns the total supply at the end of the block at `height`.
+    /// Return type: TotalSupplyResponse.
+    TotalSupplyAtHeight {
+        height: u64,
+    },
+    /// Returns the reward token dividends claimable by the given address.
+    /// Return type: DividendsResponse.
+    Dividends {
+        address: String,
+    },
+    /// Returns the registered permit key and next expected nonce of the given address.
+    /// Return type: PermitNonceResponse.
+    PermitNonce {
+        owner: String,
+    },
+    /// Returns the vesting schedules of the given address with its vested and locked amounts.
+    /// Return type: VestingResponse.
+    Vesting {
+        address: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VestingResponse {
+    pub address: String,
+    pub schedules: Vec<VestingSchedule>,
+    /// Amount vested so far across all schedules
+    pub vested: Uint128,
+    /// Amount that cannot be transferred yet
+    pub locked: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PermitNonceResponse {
+    pub public_key: Option<Binary>,
+    pub nonce: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
@@ -268,6 +611,83 @@ pub struct QueryTaxResponse {
     pub after_tax: Uint128,
     pub reflection_amount: Uint128,
     pub liquidity_amount: Uint128,
+    // Fields added after the first release default when missing, so responses of older
+    // deployments still parse
+    #[serde(default)]
+    pub burn_amount: Uint128,
+    /// Effective tax rate applied, after any rebate and sell surcharge
+    #[serde(default)]
+    pub tax_rate: Decimal,
+    /// Share of the pair's token reserve sold, when sent into a registered pair
+    #[serde(default)]
+    pub sell_pool_share: Option<Decimal>,
+    /// Progressive sell tax added to the tax rate
+    #[serde(default)]
+    pub sell_surcharge: Decimal,
+    /// Whether the sender bought from a registered pair within the MEV guard window
+    #[serde(default)]
+    pub mev_detected: bool,
+    /// MEV guard surcharge added to the tax rate
+    #[serde(default)]
+    pub mev_surcharge: Decimal,
+    /// Whether the sell would take the window of the pair sold into over the circuit breaker
+    /// limit
+    #[serde(default)]
+    pub breaker_tripped: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CircuitBreakerResponse {
+    pub breaker: Option<CircuitBreaker>,
+    /// Height or time the current window opened at
+    pub window_start: u64,
+    pub volume: Uint128,
+    pub limit: Uint128,
+    /// No further sell fits in the window
+    pub tripped: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PairsResponse {
+    pub pairs: Vec<String>,
+    pub sell_tax_tiers: Vec<SellTaxTier>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct DividendsResponse {
+    pub address: String,
+    pub claimable: Uint128,
+    pub excluded: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct TotalSupplyResponse {
+    pub total_supply: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct HolderTierResponse {
+    pub address: String,
+    pub lifetime_tax_paid: Uint128,
+    /// Timestamp (seconds) since which the address has held tokens
+    pub holding_since: Option<u64>,
+    /// Index of the best rebate tier the holder qualifies for
+    pub tier: Option<u32>,
+    pub rebate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct TaxStatsResponse {
+    /// Total amount of tax deducted from transfers
+    pub total_taxed: Uint128,
+    /// Total amount credited to the treasury
+    pub total_to_treasury: Uint128,
+    /// Portion of the collected tax earmarked for burning
+    pub total_burned: Uint128,
+    /// Portion of the collected tax earmarked for reflection
+    pub total_reflected: Uint128,
+    /// Number of transfers that paid a non-zero tax
+    pub taxed_transfer_count: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
@@ -279,9 +699,48 @@ pub struct MigrateMsg {
 #[serde(rename_all = "snake_case")]
 pub enum TreasuryExecuteMsg {
     Liquify {},
+    PayDividend { recipient: String, amount: Uint128 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TreasuryQueryMsg {
+    LpLock {},
+}
+
+/// The part of the treasury's `LpLock` response the token reads
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TreasuryLpLockResponse {
+    /// Time the whole LP balance is released, not set without a lock
+    pub release_end: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct GetTreasuryResponse {
     pub address: String,
 }
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TreasuryRatesResponse {
+    pub reflection_rate: Decimal,
+    pub burn_rate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TaxDestinationsResponse {
+    pub destinations: Vec<TaxDestinationInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TaxDestinationInfo {
+    pub address: String,
+    pub share: Decimal,
+    pub total_received: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct GetTaxPolicyResponse {
+    pub contract: Option<String>,
+    /// Code ids a tax policy contract may be instantiated from
+    pub code_ids: Vec<u64>,
+}
@@ -1,11 +1,27 @@
 #[cfg(test)]
 mod tests {
-    use crate::contract::{execute, instantiate, query, TREASURY};
-    use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+    use crate::contract::{
+        execute, instantiate, query, DIVIDEND_INDEX, DIVIDEND_SUPPLY, TREASURY,
+        UNDISTRIBUTED_DIVIDENDS,
+    };
+    use crate::msg::{
+        AntiwhaleMode, CircuitBreaker, CircuitBreakerResponse, DividendsResponse, ExecuteMsg,
+        HolderTierResponse, InstantiateMsg, MevGuard, MigrateMsg, PermitNonceResponse,
+        PermitPayload, QueryMsg, QueryTaxResponse, RebateTier, RelayAction, RelayPayload,
+        SellTaxTier, SellWindow, TaxDestinationMsg, TaxDestinationsResponse, TaxOperation,
+        TaxPolicyQueryMsg, TaxPolicyResponse, TaxStatsResponse, TotalSupplyResponse,
+        TreasuryExecuteMsg, TreasuryLpLockResponse, TreasuryQueryMsg, TreasuryRatesResponse,
+        VestingResponse, VestingSchedule,
+    };
+    use choice::asset::{Asset, AssetInfo};
     use choice::mock_querier::{mock_dependencies, WasmMockQuerier};
     use cosmwasm_std::testing::{message_info, mock_env, MockApi};
-    use cosmwasm_std::{from_json, to_json_binary, Addr, CosmosMsg, Decimal, Uint128, WasmMsg};
-    use cw20::{BalanceResponse, Cw20ReceiveMsg};
+    use cosmwasm_std::{
+        from_json, to_json_binary, to_json_vec, Addr, Binary, ContractResult, CosmosMsg, Decimal,
+        Decimal256, Querier, SystemError, SystemResult, Uint128, WasmMsg, WasmQuery,
+    };
+    use cw20::{AllowanceResponse, BalanceResponse, Cw20ReceiveMsg};
+    use ed25519_zebra::{SigningKey, VerificationKey};
     use serde::Serialize;
 
     struct TestAddresses {
@@ -23,6 +39,13 @@ mod tests {
         TestAddresses,
     ) {
         let mut deps = mock_dependencies(&[]);
+        let (env, addrs) = setup_contract(&mut deps);
+        (deps, env, addrs)
+    }
+
+    fn setup_contract<Q: Querier>(
+        deps: &mut cosmwasm_std::OwnedDeps<cosmwasm_std::MemoryStorage, MockApi, Q>,
+    ) -> (cosmwasm_std::Env, TestAddresses) {
         let env = mock_env();
 
         let addrs = TestAddresses {
@@ -47,6 +70,8 @@ mod tests {
             router: "router_address".to_string(),
             mint: None,
             marketing: None,
+            enable_snapshots: None,
+            vesting: None,
         };
 
         let info = message_info(&addrs.admin, &[]);
@@ -64,7 +89,7 @@ mod tests {
         };
         execute(deps.as_mut(), env.clone(), tax_info, tax_msg).unwrap();
 
-        (deps, env, addrs)
+        (env, addrs)
     }
 
     // --- REVISED: The query_balance helper is much simpler ---
@@ -301,4 +326,1590 @@ mod tests {
             Uint128::new(90_000)
         );
     }
+
+    #[test]
+    fn test_tax_stats_track_taxed_transfers() {
+        let (mut deps, env, addrs) = setup_test();
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info,
+            ExecuteMsg::SetTaxRate {
+                global_rate: Decimal::percent(10),
+                reflection_rate: Decimal::percent(50),
+                burn_rate: Decimal::percent(20),
+                antiwhale_rate: Decimal::one(),
+            },
+        )
+        .unwrap();
+
+        let user_info = message_info(&addrs.user_a, &[]);
+        for _ in 0..2 {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                user_info.clone(),
+                ExecuteMsg::Transfer {
+                    recipient: addrs.user_b.to_string(),
+                    amount: Uint128::new(100_000),
+                },
+            )
+            .unwrap();
+        }
+
+        let stats: TaxStatsResponse =
+            from_json(query(deps.as_ref(), env, QueryMsg::TaxStats {}).unwrap()).unwrap();
+        assert_eq!(
+            stats,
+            TaxStatsResponse {
+                total_taxed: Uint128::new(20_000),
+                total_to_treasury: Uint128::new(20_000),
+                total_burned: Uint128::new(4_000),
+                total_reflected: Uint128::new(10_000),
+                taxed_transfer_count: 2,
+            }
+        );
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.treasury),
+            Uint128::new(20_000)
+        );
+    }
+
+    #[test]
+    fn test_loyalty_rebate_tiers() {
+        let (mut deps, mut env, addrs) = setup_test();
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        let tiers = vec![
+            RebateTier {
+                min_tax_paid: Some(Uint128::new(10_000)),
+                min_holding_days: None,
+                rebate: Decimal::percent(50),
+            },
+            RebateTier {
+                min_tax_paid: None,
+                min_holding_days: Some(30),
+                rebate: Decimal::percent(80),
+            },
+        ];
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&addrs.user_a, &[]),
+            ExecuteMsg::SetRebateTiers {
+                tiers: tiers.clone(),
+            },
+        );
+        assert!(res.is_err());
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info,
+            ExecuteMsg::SetRebateTiers { tiers },
+        )
+        .unwrap();
+
+        // First transfer pays the full 10% and unlocks the first tier
+        let user_info = message_info(&addrs.user_a, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_info.clone(),
+            ExecuteMsg::Transfer {
+                recipient: addrs.user_b.to_string(),
+                amount: Uint128::new(100_000),
+            },
+        )
+        .unwrap();
+
+        let tier: HolderTierResponse = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::HolderTier {
+                    address: addrs.user_a.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(tier.lifetime_tax_paid, Uint128::new(10_000));
+        assert_eq!(tier.tier, Some(0));
+        assert_eq!(tier.rebate, Decimal::percent(50));
+
+        let tax: QueryTaxResponse = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::QueryTax {
+                    amount: Uint128::new(100_000),
+                    sender: Some(addrs.user_a.to_string()),
+                    recipient: None,
+                    operation: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(tax.tax_rate, Decimal::percent(5));
+        assert_eq!(tax.taxed_amount, Uint128::new(5_000));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_info.clone(),
+            ExecuteMsg::Transfer {
+                recipient: addrs.user_b.to_string(),
+                amount: Uint128::new(100_000),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.user_b),
+            Uint128::new(185_000)
+        );
+
+        // Holding for 30 days unlocks the better tier
+        env.block.time = env.block.time.plus_days(30);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_info,
+            ExecuteMsg::Transfer {
+                recipient: addrs.user_b.to_string(),
+                amount: Uint128::new(100_000),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.user_b),
+            Uint128::new(283_000)
+        );
+    }
+
+    #[test]
+    fn test_balance_and_supply_snapshots() {
+        let (mut deps, mut env, addrs) = setup_test();
+        let start = env.block.height;
+
+        // Disabled by default
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::TotalSupplyAtHeight { height: start },
+        );
+        assert!(res.is_err());
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info,
+            ExecuteMsg::SetSnapshots { enabled: true },
+        )
+        .unwrap();
+
+        let user_info = message_info(&addrs.user_a, &[]);
+        env.block.height = start + 1;
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_info.clone(),
+            ExecuteMsg::Transfer {
+                recipient: addrs.user_b.to_string(),
+                amount: Uint128::new(100_000),
+            },
+        )
+        .unwrap();
+
+        env.block.height = start + 3;
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_info,
+            ExecuteMsg::Burn {
+                amount: Uint128::new(100_000),
+            },
+        )
+        .unwrap();
+
+        env.block.height = start + 10;
+        let balance_at = |address: &Addr, height: u64| -> Uint128 {
+            let res: BalanceResponse = from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::BalanceAtHeight {
+                        address: address.to_string(),
+                        height,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            res.balance
+        };
+        let supply_at = |height: u64| -> Uint128 {
+            let res: TotalSupplyResponse = from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::TotalSupplyAtHeight { height },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            res.total_supply
+        };
+
+        assert_eq!(balance_at(&addrs.user_a, start), Uint128::new(1_000_000));
+        assert_eq!(balance_at(&addrs.user_a, start + 1), Uint128::new(900_000));
+        assert_eq!(balance_at(&addrs.user_a, start + 2), Uint128::new(900_000));
+        assert_eq!(balance_at(&addrs.user_a, start + 3), Uint128::new(800_000));
+        assert_eq!(balance_at(&addrs.user_b, start), Uint128::zero());
+        assert_eq!(balance_at(&addrs.user_b, start + 5), Uint128::new(90_000));
+
+        assert_eq!(supply_at(start + 2), Uint128::new(1_000_000));
+        assert_eq!(supply_at(start + 3), Uint128::new(900_000));
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::BalanceAtHeight {
+                address: addrs.user_a.to_string(),
+                height: start - 1,
+            },
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_dividends_distribute_and_claim() {
+        let (mut deps, env, addrs) = setup_test();
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info,
+            ExecuteMsg::SetDividendExclusion {
+                address: addrs.treasury.to_string(),
+                excluded: true,
+            },
+        )
+        .unwrap();
+
+        // user_a 900k, user_b 90k, the excluded treasury 10k
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info,
+            ExecuteMsg::Transfer {
+                recipient: addrs.user_b.to_string(),
+                amount: Uint128::new(100_000),
+            },
+        )
+        .unwrap();
+
+        let distribute_msg = ExecuteMsg::DistributeDividends {
+            amount: Uint128::new(99_000),
+        };
+        let user_b_info = message_info(&addrs.user_b, &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            user_b_info.clone(),
+            distribute_msg.clone(),
+        );
+        assert!(res.is_err());
+        let treasury_info = message_info(&addrs.treasury, &[]);
+        execute(deps.as_mut(), env.clone(), treasury_info, distribute_msg).unwrap();
+
+        let claimable = |deps: cosmwasm_std::Deps, address: &Addr| -> Uint128 {
+            let res: DividendsResponse = from_json(
+                query(
+                    deps,
+                    env.clone(),
+                    QueryMsg::Dividends {
+                        address: address.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            res.claimable
+        };
+        assert_eq!(
+            claimable(deps.as_ref(), &addrs.user_a),
+            Uint128::new(90_000)
+        );
+        assert_eq!(claimable(deps.as_ref(), &addrs.user_b), Uint128::new(9_000));
+        assert_eq!(claimable(deps.as_ref(), &addrs.treasury), Uint128::zero());
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            user_b_info.clone(),
+            ExecuteMsg::Claim {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: addrs.treasury.to_string(),
+                msg: to_json_binary(&TreasuryExecuteMsg::PayDividend {
+                    recipient: addrs.user_b.to_string(),
+                    amount: Uint128::new(9_000),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            user_b_info,
+            ExecuteMsg::Claim {},
+        );
+        assert!(res.is_err());
+        assert_eq!(claimable(deps.as_ref(), &addrs.user_b), Uint128::zero());
+    }
+
+    #[test]
+    fn test_dividend_remainder_carried() {
+        let (mut deps, env, addrs) = setup_test();
+        let treasury_info = message_info(&addrs.treasury, &[]);
+        // An 18 decimal token with a billion tokens eligible
+        let supply = Uint128::new(1_000_000_000_000_000_000_000_000_000);
+        DIVIDEND_SUPPLY
+            .save(deps.as_mut().storage, &supply)
+            .unwrap();
+
+        // Below one unit of the index per token, the reward waits instead of being lost
+        let distribute = |amount: u128| ExecuteMsg::DistributeDividends {
+            amount: Uint128::new(amount),
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            treasury_info.clone(),
+            distribute(100_000_000),
+        )
+        .unwrap();
+        assert_eq!(
+            DIVIDEND_INDEX.may_load(&deps.storage).unwrap(),
+            Some(Decimal256::zero())
+        );
+        assert_eq!(
+            UNDISTRIBUTED_DIVIDENDS.load(&deps.storage).unwrap(),
+            Uint128::new(100_000_000)
+        );
+
+        // The floor remainder of a larger reward is carried as well
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            treasury_info,
+            distribute(1_000_000_000),
+        )
+        .unwrap();
+        assert_eq!(
+            DIVIDEND_INDEX.load(&deps.storage).unwrap(),
+            Decimal256::from_atomics(1u128, 18).unwrap()
+        );
+        assert_eq!(
+            UNDISTRIBUTED_DIVIDENDS.load(&deps.storage).unwrap(),
+            Uint128::new(100_000_000)
+        );
+        assert!(res
+            .attributes
+            .contains(&cosmwasm_std::attr("amount", "1000000000")));
+    }
+
+    #[test]
+    fn test_permit_sets_allowance() {
+        let (mut deps, env, addrs) = setup_test();
+
+        let signing_key = SigningKey::from([7u8; 32]);
+        let public_key: [u8; 32] = VerificationKey::from(&signing_key).into();
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info,
+            ExecuteMsg::RegisterPermitKey {
+                public_key: Binary::from(public_key),
+            },
+        )
+        .unwrap();
+
+        let payload = PermitPayload {
+            chain_id: env.block.chain_id.clone(),
+            contract: env.contract.address.to_string(),
+            owner: addrs.user_a.to_string(),
+            spender: addrs.user_b.to_string(),
+            amount: Uint128::new(5_000),
+            expires: None,
+            nonce: 0,
+        };
+        let sign = |payload: &PermitPayload| -> Binary {
+            let signature = signing_key.sign(&to_json_vec(payload).unwrap());
+            Binary::from(signature.to_bytes())
+        };
+        let permit_msg = |payload: &PermitPayload, signature: Binary| ExecuteMsg::Permit {
+            owner: payload.owner.clone(),
+            spender: payload.spender.clone(),
+            amount: payload.amount,
+            expires: payload.expires,
+            nonce: payload.nonce,
+            signature,
+        };
+
+        // Signed for another chain
+        let foreign = PermitPayload {
+            chain_id: "other-chain".to_string(),
+            ..payload.clone()
+        };
+        let relayer_info = message_info(&addrs.aggregator, &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            relayer_info.clone(),
+            permit_msg(&payload, sign(&foreign)),
+        );
+        assert!(res.is_err());
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            relayer_info.clone(),
+            permit_msg(&payload, sign(&payload)),
+        )
+        .unwrap();
+
+        let allowance: AllowanceResponse = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::Allowance {
+                    owner: addrs.user_a.to_string(),
+                    spender: addrs.user_b.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(allowance.allowance, Uint128::new(5_000));
+
+        // Replaying the same permit fails
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            relayer_info,
+            permit_msg(&payload, sign(&payload)),
+        );
+        assert!(res.is_err());
+
+        let nonce: PermitNonceResponse = from_json(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::PermitNonce {
+                    owner: addrs.user_a.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(nonce.nonce, 1);
+        assert_eq!(nonce.public_key, Some(Binary::from(public_key)));
+    }
+
+    #[test]
+    fn test_relay_transfer_pays_relayer_fee() {
+        let (mut deps, env, addrs) = setup_test();
+
+        let signing_key = SigningKey::from([9u8; 32]);
+        let public_key: [u8; 32] = VerificationKey::from(&signing_key).into();
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info,
+            ExecuteMsg::RegisterPermitKey {
+                public_key: Binary::from(public_key),
+            },
+        )
+        .unwrap();
+
+        let payload = RelayPayload {
+            chain_id: env.block.chain_id.clone(),
+            contract: env.contract.address.to_string(),
+            owner: addrs.user_a.to_string(),
+            action: RelayAction::Transfer {
+                recipient: addrs.user_b.to_string(),
+                amount: Uint128::new(100_000),
+            },
+            fee: Some(Uint128::new(1_000)),
+            relayer: None,
+            nonce: 0,
+            deadline: env.block.time.seconds() + 60,
+        };
+        let relay_msg = |payload: &RelayPayload| {
+            let signature = signing_key.sign(&to_json_vec(payload).unwrap());
+            ExecuteMsg::RelayTransfer {
+                owner: payload.owner.clone(),
+                action: payload.action.clone(),
+                fee: payload.fee,
+                relayer: payload.relayer.clone(),
+                nonce: payload.nonce,
+                deadline: payload.deadline,
+                signature: Binary::from(signature.to_bytes()),
+            }
+        };
+
+        let relayer_info = message_info(&addrs.aggregator, &[]);
+        let expired = RelayPayload {
+            deadline: env.block.time.seconds() - 1,
+            ..payload.clone()
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            relayer_info.clone(),
+            relay_msg(&expired),
+        );
+        assert!(res.is_err());
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            relayer_info.clone(),
+            relay_msg(&payload),
+        )
+        .unwrap();
+        // The events of the fee transfer are kept with those of the relayed transfer
+        let transfers = res
+            .attributes
+            .iter()
+            .filter(|attr| attr.key == "action" && attr.value == "transfer")
+            .count();
+        assert_eq!(transfers, 2);
+
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.user_a),
+            Uint128::new(899_000)
+        );
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.user_b),
+            Uint128::new(90_000)
+        );
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.aggregator),
+            Uint128::new(900)
+        );
+
+        // The nonce is consumed
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            relayer_info.clone(),
+            relay_msg(&payload),
+        );
+        assert!(res.is_err());
+
+        // A payload bound to a relayer can only be submitted by it
+        let bound = RelayPayload {
+            relayer: Some(addrs.user_b.to_string()),
+            nonce: 1,
+            ..payload.clone()
+        };
+        let res = execute(deps.as_mut(), env.clone(), relayer_info, relay_msg(&bound));
+        assert!(res.is_err());
+        let user_b_info = message_info(&addrs.user_b, &[]);
+        execute(deps.as_mut(), env, user_b_info, relay_msg(&bound)).unwrap();
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.user_b),
+            Uint128::new(180_900)
+        );
+    }
+
+    #[test]
+    fn test_vesting_locks_balance() {
+        let (mut deps, mut env, addrs) = setup_test();
+
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info,
+            ExecuteMsg::Transfer {
+                recipient: addrs.admin.to_string(),
+                amount: Uint128::new(200_000),
+            },
+        )
+        .unwrap();
+
+        let now = env.block.time.seconds();
+        let admin_info = message_info(&addrs.admin, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info,
+            ExecuteMsg::CreateVesting {
+                schedule: VestingSchedule {
+                    address: addrs.user_b.to_string(),
+                    amount: Uint128::new(100_000),
+                    start_time: now,
+                    cliff_time: now + 100,
+                    end_time: now + 1_000,
+                },
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.user_b),
+            Uint128::new(100_000)
+        );
+
+        // Nothing is transferable or burnable before the cliff
+        let user_b_info = message_info(&addrs.user_b, &[]);
+        let transfer = |amount: u128| ExecuteMsg::Transfer {
+            recipient: addrs.user_a.to_string(),
+            amount: Uint128::new(amount),
+        };
+        let res = execute(deps.as_mut(), env.clone(), user_b_info.clone(), transfer(1));
+        assert!(res.is_err());
+
+        env.block.time = env.block.time.plus_seconds(500);
+        let vesting: VestingResponse = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::Vesting {
+                    address: addrs.user_b.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(vesting.vested, Uint128::new(50_000));
+        assert_eq!(vesting.locked, Uint128::new(50_000));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_b_info.clone(),
+            transfer(50_000),
+        )
+        .unwrap();
+        let res = execute(deps.as_mut(), env.clone(), user_b_info.clone(), transfer(1));
+        assert!(res.is_err());
+        let res = execute(
+            deps.as_mut(),
+            env,
+            user_b_info,
+            ExecuteMsg::Burn {
+                amount: Uint128::new(1),
+            },
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_tax_destinations_split() {
+        let (mut deps, env, addrs) = setup_test();
+        let marketing = deps.api.addr_make("marketing");
+        let dev = deps.api.addr_make("dev");
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info.clone(),
+            ExecuteMsg::SetTaxDestinations {
+                destinations: vec![
+                    TaxDestinationMsg {
+                        address: marketing.to_string(),
+                        share: Decimal::percent(60),
+                    },
+                    TaxDestinationMsg {
+                        address: dev.to_string(),
+                        share: Decimal::percent(50),
+                    },
+                ],
+            },
+        );
+        assert!(res.is_err());
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info,
+            ExecuteMsg::SetTaxDestinations {
+                destinations: vec![
+                    TaxDestinationMsg {
+                        address: marketing.to_string(),
+                        share: Decimal::percent(20),
+                    },
+                    TaxDestinationMsg {
+                        address: dev.to_string(),
+                        share: Decimal::percent(10),
+                    },
+                ],
+            },
+        )
+        .unwrap();
+
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info,
+            ExecuteMsg::Transfer {
+                recipient: addrs.user_b.to_string(),
+                amount: Uint128::new(100_000),
+            },
+        )
+        .unwrap();
+        // One transfer event for the treasury and one per destination
+        assert_eq!(res.messages.len(), 3);
+
+        assert_eq!(
+            query_balance(&deps.as_mut(), &marketing),
+            Uint128::new(2_000)
+        );
+        assert_eq!(query_balance(&deps.as_mut(), &dev), Uint128::new(1_000));
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.treasury),
+            Uint128::new(7_000)
+        );
+
+        let res: TaxDestinationsResponse =
+            from_json(query(deps.as_ref(), env, QueryMsg::TaxDestinations {}).unwrap()).unwrap();
+        assert_eq!(res.destinations[0].total_received, Uint128::new(2_000));
+        assert_eq!(res.destinations[1].total_received, Uint128::new(1_000));
+    }
+
+    #[test]
+    fn test_immediate_burn_reduces_supply() {
+        let (mut deps, env, addrs) = setup_test();
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info.clone(),
+            ExecuteMsg::SetTaxRate {
+                global_rate: Decimal::percent(10),
+                reflection_rate: Decimal::percent(25),
+                burn_rate: Decimal::percent(50),
+                antiwhale_rate: Decimal::one(),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info,
+            ExecuteMsg::SetImmediateBurn { enabled: true },
+        )
+        .unwrap();
+
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info,
+            ExecuteMsg::Transfer {
+                recipient: addrs.user_b.to_string(),
+                amount: Uint128::new(100_000),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.treasury),
+            Uint128::new(5_000)
+        );
+        let token_info: cw20::TokenInfoResponse =
+            from_json(query(deps.as_ref(), env.clone(), QueryMsg::TokenInfo {}).unwrap()).unwrap();
+        assert_eq!(token_info.total_supply, Uint128::new(995_000));
+
+        // The treasury only reflects what it holds and burns nothing more
+        let rates: TreasuryRatesResponse =
+            from_json(query(deps.as_ref(), env, QueryMsg::TreasuryRates {}).unwrap()).unwrap();
+        assert_eq!(rates.burn_rate, Decimal::zero());
+        assert_eq!(rates.reflection_rate, Decimal::percent(50));
+    }
+
+    #[test]
+    fn test_progressive_sell_tax() {
+        let (mut deps, env, addrs) = setup_test();
+        deps.querier.with_pool(
+            addrs.pair.as_str(),
+            [
+                Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: env.contract.address.to_string(),
+                    },
+                    amount: Uint128::new(400_000),
+                },
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "inj".to_string(),
+                    },
+                    amount: Uint128::new(1_000),
+                },
+            ],
+        );
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info.clone(),
+            ExecuteMsg::RegisterPair {
+                address: addrs.pair.to_string(),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info,
+            ExecuteMsg::SetSellTaxTiers {
+                tiers: vec![
+                    SellTaxTier {
+                        min_pool_share: Decimal::percent(5),
+                        extra_rate: Decimal::percent(5),
+                    },
+                    SellTaxTier {
+                        min_pool_share: Decimal::percent(20),
+                        extra_rate: Decimal::percent(20),
+                    },
+                ],
+            },
+        )
+        .unwrap();
+
+        let simulate = |deps: cosmwasm_std::Deps, amount: u128| -> QueryTaxResponse {
+            from_json(
+                query(
+                    deps,
+                    env.clone(),
+                    QueryMsg::QueryTax {
+                        amount: Uint128::new(amount),
+                        sender: Some(addrs.user_a.to_string()),
+                        recipient: Some(addrs.pair.to_string()),
+                        operation: Some(TaxOperation::Send),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        let small = simulate(deps.as_ref(), 10_000);
+        assert_eq!(small.sell_surcharge, Decimal::zero());
+        assert_eq!(small.taxed_amount, Uint128::new(1_000));
+
+        let large = simulate(deps.as_ref(), 100_000);
+        assert_eq!(large.sell_pool_share, Some(Decimal::percent(25)));
+        assert_eq!(large.sell_surcharge, Decimal::percent(20));
+        assert_eq!(large.taxed_amount, Uint128::new(30_000));
+
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        execute(
+            deps.as_mut(),
+            env,
+            user_a_info,
+            ExecuteMsg::Send {
+                contract: addrs.pair.to_string(),
+                amount: Uint128::new(100_000),
+                msg: to_json_binary(&"swap").unwrap(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            query_balance(&deps.as_mut(), &addrs.pair),
+            Uint128::new(70_000)
+        );
+    }
+
+    #[test]
+    fn test_pool_reserve_antiwhale_limits_sells() {
+        let (mut deps, env, addrs) = setup_test();
+        let token = env.contract.address.to_string();
+        let pair = addrs.pair.to_string();
+        // The limit follows the pair's reported reserve, not its raw cw20 balance
+        deps.querier
+            .with_token_balances(&[(&token, &[(&pair, &Uint128::new(4_000_000))])]);
+        deps.querier.with_pool(
+            &pair,
+            [
+                Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: token.clone(),
+                    },
+                    amount: Uint128::new(400_000),
+                },
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "inj".to_string(),
+                    },
+                    amount: Uint128::new(1_000),
+                },
+            ],
+        );
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        for msg in [
+            ExecuteMsg::SetTaxRate {
+                global_rate: Decimal::percent(10),
+                reflection_rate: Decimal::zero(),
+                burn_rate: Decimal::zero(),
+                antiwhale_rate: Decimal::percent(1),
+            },
+            ExecuteMsg::RegisterPair {
+                address: pair.clone(),
+            },
+            ExecuteMsg::SetAntiwhaleMode {
+                mode: AntiwhaleMode::PoolReserve {
+                    max_pool_share: Decimal::percent(10),
+                },
+            },
+        ] {
+            execute(deps.as_mut(), env.clone(), admin_info.clone(), msg).unwrap();
+        }
+
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        let sell = |amount: u128| ExecuteMsg::Send {
+            contract: pair.clone(),
+            amount: Uint128::new(amount),
+            msg: to_json_binary(&"swap").unwrap(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info.clone(),
+            sell(50_000),
+        );
+        assert!(res.is_err());
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info.clone(),
+            sell(30_000),
+        )
+        .unwrap();
+
+        // Transfers that are not sells ignore the supply based limit in this mode
+        execute(
+            deps.as_mut(),
+            env,
+            user_a_info,
+            ExecuteMsg::Transfer {
+                recipient: addrs.user_b.to_string(),
+                amount: Uint128::new(500_000),
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_mev_guard_surcharges_quick_sells() {
+        let (mut deps, mut env, addrs) = setup_test();
+        let pair = addrs.pair.to_string();
+
+        let guard = |reject: bool| ExecuteMsg::SetMevGuard {
+            guard: Some(MevGuard {
+                window_blocks: 1,
+                surcharge: Decimal::percent(20),
+                reject,
+            }),
+        };
+        let admin_info = message_info(&addrs.admin, &[]);
+        for msg in [
+            ExecuteMsg::RegisterPair {
+                address: pair.clone(),
+            },
+            guard(false),
+        ] {
+            execute(deps.as_mut(), env.clone(), admin_info.clone(), msg).unwrap();
+        }
+
+        let transfer = |recipient: &Addr, amount: u128| ExecuteMsg::Transfer {
+            recipient: recipient.to_string(),
+            amount: Uint128::new(amount),
+        };
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        let pair_info = message_info(&addrs.pair, &[]);
+        let user_b_info = message_info(&addrs.user_b, &[]);
+        let detected = |res: &cosmwasm_std::Response| {
+            res.attributes
+                .iter()
+                .find(|attr| attr.key == "mev_detected")
+                .map(|attr| attr.value.clone())
+        };
+
+        // user_a never bought, its sell is taxed normally
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info,
+            transfer(&addrs.pair, 100_000),
+        )
+        .unwrap();
+        assert_eq!(detected(&res), Some("false".to_string()));
+
+        // user_b buys and sells back in the same block
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            pair_info.clone(),
+            transfer(&addrs.user_b, 50_000),
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            user_b_info.clone(),
+            transfer(&addrs.pair, 20_000),
+        )
+        .unwrap();
+        assert_eq!(detected(&res), Some("true".to_string()));
+        assert!(res
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "post_tax_amount" && attr.value == "14000"));
+
+        // Past the window the surcharge no longer applies
+        env.block.height += 2;
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            user_b_info.clone(),
+            transfer(&addrs.pair, 10_000),
+        )
+        .unwrap();
+        assert_eq!(detected(&res), Some("false".to_string()));
+        assert!(res
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "post_tax_amount" && attr.value == "9000"));
+
+        // In rejection mode the quick sell fails
+        execute(deps.as_mut(), env.clone(), admin_info, guard(true)).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            pair_info,
+            transfer(&addrs.user_b, 10_000),
+        )
+        .unwrap();
+        env.block.height += 1;
+        let res = execute(
+            deps.as_mut(),
+            env,
+            user_b_info,
+            transfer(&addrs.pair, 10_000),
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_sell_volume_circuit_breaker() {
+        let (mut deps, mut env, addrs) = setup_test();
+        let pair = addrs.pair.to_string();
+        deps.querier.with_pool(
+            &pair,
+            [
+                Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: env.contract.address.to_string(),
+                    },
+                    amount: Uint128::new(400_000),
+                },
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "inj".to_string(),
+                    },
+                    amount: Uint128::new(1_000),
+                },
+            ],
+        );
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        for msg in [
+            ExecuteMsg::RegisterPair {
+                address: pair.clone(),
+            },
+            ExecuteMsg::SetCircuitBreaker {
+                breaker: Some(CircuitBreaker {
+                    window: SellWindow::Block {},
+                    max_pool_share: Decimal::percent(10),
+                    penalty_rate: None,
+                }),
+            },
+        ] {
+            execute(deps.as_mut(), env.clone(), admin_info.clone(), msg).unwrap();
+        }
+
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        let sell = |amount: u128| ExecuteMsg::Transfer {
+            recipient: pair.clone(),
+            amount: Uint128::new(amount),
+        };
+        let breaker =
+            |deps: cosmwasm_std::Deps, env: &cosmwasm_std::Env| -> CircuitBreakerResponse {
+                from_json(
+                    query(
+                        deps,
+                        env.clone(),
+                        QueryMsg::CircuitBreaker { pair: pair.clone() },
+                    )
+                    .unwrap(),
+                )
+                .unwrap()
+            };
+
+        // Sells fit until the 40_000 limit, the one that would go over it is rejected
+        for amount in [30_000, 10_000] {
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                user_a_info.clone(),
+                sell(amount),
+            )
+            .unwrap();
+            assert!(res
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "circuit_breaker_tripped" && attr.value == "false"));
+            if amount == 30_000 {
+                let res = execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    user_a_info.clone(),
+                    sell(20_000),
+                );
+                assert!(res.is_err());
+            }
+        }
+        let state = breaker(deps.as_ref(), &env);
+        assert_eq!(state.limit, Uint128::new(40_000));
+        assert_eq!(state.volume, Uint128::new(40_000));
+        assert!(state.tripped);
+        let res = execute(deps.as_mut(), env.clone(), user_a_info.clone(), sell(1_000));
+        assert!(res.is_err());
+
+        // The admin can untrip it within the window
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info,
+            ExecuteMsg::ResetCircuitBreaker { pair: None },
+        )
+        .unwrap();
+        assert!(!breaker(deps.as_ref(), &env).tripped);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info.clone(),
+            sell(40_000),
+        )
+        .unwrap();
+
+        // And it resets by itself in the next window
+        env.block.height += 1;
+        let state = breaker(deps.as_ref(), &env);
+        assert_eq!(state.volume, Uint128::zero());
+        assert!(!state.tripped);
+        execute(deps.as_mut(), env, user_a_info, sell(1_000)).unwrap();
+    }
+
+    #[test]
+    fn test_circuit_breaker_ignores_whitelisted_sells() {
+        let (mut deps, env, addrs) = setup_test();
+        let pair = addrs.pair.to_string();
+        deps.querier.with_pool(
+            &pair,
+            [
+                Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: env.contract.address.to_string(),
+                    },
+                    amount: Uint128::new(400_000),
+                },
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "inj".to_string(),
+                    },
+                    amount: Uint128::new(1_000),
+                },
+            ],
+        );
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        for msg in [
+            ExecuteMsg::RegisterPair {
+                address: pair.clone(),
+            },
+            ExecuteMsg::SetCircuitBreaker {
+                breaker: Some(CircuitBreaker {
+                    window: SellWindow::Block {},
+                    max_pool_share: Decimal::percent(10),
+                    penalty_rate: None,
+                }),
+            },
+            ExecuteMsg::SetWhitelist {
+                user: addrs.treasury.to_string(),
+                enable: true,
+            },
+        ] {
+            execute(deps.as_mut(), env.clone(), admin_info.clone(), msg).unwrap();
+        }
+
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        let sell = |amount: u128| ExecuteMsg::Transfer {
+            recipient: pair.clone(),
+            amount: Uint128::new(amount),
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            user_a_info.clone(),
+            ExecuteMsg::Transfer {
+                recipient: addrs.treasury.to_string(),
+                amount: Uint128::new(100_000),
+            },
+        )
+        .unwrap();
+
+        // A liquify sized treasury sell goes over the 40_000 limit without counting towards it
+        let treasury_info = message_info(&addrs.treasury, &[]);
+        execute(deps.as_mut(), env.clone(), treasury_info, sell(100_000)).unwrap();
+        let state: CircuitBreakerResponse = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::CircuitBreaker { pair: pair.clone() },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(state.volume, Uint128::zero());
+        assert!(!state.tripped);
+
+        // So user sells in the same window still get the whole limit
+        for amount in [30_000, 10_000] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                user_a_info.clone(),
+                sell(amount),
+            )
+            .unwrap();
+        }
+        let res = execute(deps.as_mut(), env, user_a_info, sell(1_000));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_tax_policy_contract_with_fallback() {
+        let mut deps = cosmwasm_std::testing::mock_dependencies();
+        let (env, addrs) = setup_contract(&mut deps);
+        let policy = deps.api.addr_make("policy");
+        let broken_policy = deps.api.addr_make("broken_policy");
+        let bloated_policy = deps.api.addr_make("bloated_policy");
+        let migratable_policy = deps.api.addr_make("migratable_policy");
+        let other_code_policy = deps.api.addr_make("other_code_policy");
+
+        // Sends to the pair pay 25%, a broken policy errors and a bloated one answers too much
+        let pair = addrs.pair.to_string();
+        let policy_addr = policy.to_string();
+        let bloated_addr = bloated_policy.to_string();
+        let migratable_addr = migratable_policy.to_string();
+        let other_code_addr = other_code_policy.to_string();
+        let creator = addrs.admin.clone();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::ContractInfo { contract_addr } => {
+                let code_id = if *contract_addr == other_code_addr {
+                    8
+                } else {
+                    7
+                };
+                let admin = (*contract_addr == migratable_addr).then(|| creator.clone());
+                SystemResult::Ok(ContractResult::Ok(
+                    to_json_binary(&cosmwasm_std::ContractInfoResponse::new(
+                        code_id,
+                        creator.clone(),
+                        admin,
+                        false,
+                        None,
+                    ))
+                    .unwrap(),
+                ))
+            }
+            WasmQuery::Smart { contract_addr, .. } if *contract_addr == bloated_addr => {
+                SystemResult::Ok(ContractResult::Ok(Binary::from(vec![b' '; 1024])))
+            }
+            WasmQuery::Smart { contract_addr, msg } if *contract_addr == policy_addr => {
+                let TaxPolicyQueryMsg::TaxPolicy {
+                    recipient,
+                    operation,
+                    ..
+                } = from_json(msg).unwrap();
+                let tax_rate = if recipient == Some(pair.clone()) && operation == TaxOperation::Send
+                {
+                    Decimal::percent(25)
+                } else {
+                    Decimal::percent(2)
+                };
+                SystemResult::Ok(ContractResult::Ok(
+                    to_json_binary(&TaxPolicyResponse {
+                        tax_rate,
+                        reflection_rate: Decimal::percent(40),
+                        burn_rate: Decimal::percent(10),
+                    })
+                    .unwrap(),
+                ))
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract {
+                addr: "unknown".to_string(),
+            }),
+        });
+
+        let simulate = |deps: cosmwasm_std::Deps, recipient: &Addr, operation: TaxOperation| {
+            let res: QueryTaxResponse = from_json(
+                query(
+                    deps,
+                    env.clone(),
+                    QueryMsg::QueryTax {
+                        amount: Uint128::new(100_000),
+                        sender: Some(addrs.user_a.to_string()),
+                        recipient: Some(recipient.to_string()),
+                        operation: Some(operation),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            res.taxed_amount
+        };
+
+        let admin_info = message_info(&addrs.admin, &[]);
+        let set_policy = |contract: &Addr| ExecuteMsg::SetTaxPolicy {
+            contract: Some(contract.to_string()),
+        };
+        let user_info = message_info(&addrs.user_a, &[]);
+        let res = execute(deps.as_mut(), env.clone(), user_info, set_policy(&policy));
+        assert!(res.is_err());
+
+        // Only immutable contracts of an allowed code id can be registered
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info.clone(),
+            set_policy(&policy),
+        );
+        assert!(res.is_err());
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info.clone(),
+            ExecuteMsg::SetTaxPolicyCodes { code_ids: vec![7] },
+        )
+        .unwrap();
+        for rejected in [&other_code_policy, &migratable_policy] {
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                admin_info.clone(),
+                set_policy(rejected),
+            );
+            assert!(res.is_err());
+        }
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info.clone(),
+            set_policy(&policy),
+        )
+        .unwrap();
+        assert_eq!(
+            simulate(deps.as_ref(), &addrs.pair, TaxOperation::Send),
+            Uint128::new(25_000)
+        );
+        assert_eq!(
+            simulate(deps.as_ref(), &addrs.pair, TaxOperation::Transfer),
+            Uint128::new(2_000)
+        );
+
+        // The treasury splits what it received with the policy's rates until it liquifies
+        let treasury_rates = |deps: cosmwasm_std::Deps| -> TreasuryRatesResponse {
+            from_json(query(deps, env.clone(), QueryMsg::TreasuryRates {}).unwrap()).unwrap()
+        };
+        let built_in = treasury_rates(deps.as_ref());
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&addrs.user_a, &[]),
+            ExecuteMsg::Transfer {
+                recipient: addrs.user_b.to_string(),
+                amount: Uint128::new(100_000),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            treasury_rates(deps.as_ref()),
+            TreasuryRatesResponse {
+                reflection_rate: Decimal::percent(40),
+                burn_rate: Decimal::percent(10),
+            }
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&addrs.user_a, &[]),
+            ExecuteMsg::TreasuryLiquified {},
+        );
+        assert!(res.is_err());
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&addrs.treasury, &[]),
+            ExecuteMsg::TreasuryLiquified {},
+        )
+        .unwrap();
+        assert_eq!(treasury_rates(deps.as_ref()), built_in);
+
+        // Falls back to the built-in 10% when the policy fails or answers too much
+        for fallback in [&broken_policy, &bloated_policy] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                admin_info.clone(),
+                set_policy(fallback),
+            )
+            .unwrap();
+            assert_eq!(
+                simulate(deps.as_ref(), &addrs.pair, TaxOperation::Send),
+                Uint128::new(10_000)
+            );
+        }
+    }
+
+    #[test]
+    fn test_migrate_treasury_waits_for_lp_lock() {
+        let mut deps = cosmwasm_std::testing::mock_dependencies();
+        let (mut env, addrs) = setup_contract(&mut deps);
+        let release_end = env.block.time.seconds() + 1_000;
+        let treasury = addrs.treasury.to_string();
+        let answers = treasury.clone();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, msg } if *contract_addr == answers => {
+                let TreasuryQueryMsg::LpLock {} = from_json(msg).unwrap();
+                SystemResult::Ok(ContractResult::Ok(
+                    to_json_binary(&TreasuryLpLockResponse {
+                        release_end: Some(release_end),
+                    })
+                    .unwrap(),
+                ))
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            }),
+        });
+
+        let migrate = ExecuteMsg::MigrateTreasury { code_id: 9 };
+        let user_a_info = message_info(&addrs.user_a, &[]);
+        let res = execute(deps.as_mut(), env.clone(), user_a_info, migrate.clone());
+        assert!(res.unwrap_err().to_string().contains("Not admin"));
+
+        // The admin cannot swap the treasury code while its LP is still locked
+        let admin_info = message_info(&addrs.admin, &[]);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info.clone(),
+            migrate.clone(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Treasury LP is locked until"));
+
+        env.block.time = env.block.time.plus_seconds(1_000);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            admin_info.clone(),
+            migrate.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Migrate {
+                contract_addr: treasury,
+                new_code_id: 9,
+                msg: to_json_binary(&MigrateMsg {
+                    msg: "".to_string(),
+                })
+                .unwrap(),
+            })
+        );
+
+        // A treasury that predates the lock does not answer the query
+        deps.querier.update_wasm(|_| {
+            SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            })
+        });
+        env.block.time = env.block.time.minus_seconds(1_000);
+        execute(deps.as_mut(), env, admin_info, migrate).unwrap();
+    }
+
+    #[test]
+    fn test_query_tax_response_compatibility() {
+        // Responses of the first release parse with the added fields defaulted
+        let old: QueryTaxResponse = from_json(
+            br#"{"taxed_amount":"10","after_tax":"90","reflection_amount":"5","liquidity_amount":"5"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            old,
+            QueryTaxResponse {
+                taxed_amount: Uint128::new(10),
+                after_tax: Uint128::new(90),
+                reflection_amount: Uint128::new(5),
+                liquidity_amount: Uint128::new(5),
+                ..QueryTaxResponse::default()
+            }
+        );
+
+        // Consumers knowing only the first release's fields, like the treasury, ignore the rest
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct FirstReleaseResponse {
+            taxed_amount: Uint128,
+            after_tax: Uint128,
+            reflection_amount: Uint128,
+            liquidity_amount: Uint128,
+        }
+        let new = QueryTaxResponse {
+            taxed_amount: Uint128::new(10),
+            after_tax: Uint128::new(90),
+            sell_pool_share: Some(Decimal::percent(3)),
+            mev_detected: true,
+            breaker_tripped: true,
+            ..QueryTaxResponse::default()
+        };
+        let parsed: FirstReleaseResponse = from_json(to_json_binary(&new).unwrap()).unwrap();
+        assert_eq!(parsed.taxed_amount, Uint128::new(10));
+        assert_eq!(parsed.after_tax, Uint128::new(90));
+    }
 }
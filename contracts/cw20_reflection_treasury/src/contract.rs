@@ -1,25 +1,30 @@
 use std::ops::{Div, Sub};
 
-use choice::pair::SimulationResponse;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
     coin, from_json, to_json_binary, Addr, Api, BankMsg, Binary, CosmosMsg, Decimal, Deps, DepsMut,
-    Env, MessageInfo, QuerierWrapper, QueryRequest, Response, StdError, StdResult, Storage,
-    Uint128, WasmMsg, WasmQuery,
+    Env, MessageInfo, QuerierWrapper, QueryRequest, Reply, Response, StdError, StdResult, Storage,
+    SubMsg, SubMsgResult, Uint128, WasmMsg, WasmQuery,
 };
 use cw20::{BalanceResponse, Cw20ExecuteMsg};
 
 use cw2::set_contract_version;
 
 use crate::msg::{
-    Cw20HookMsg, Cw20ReceiveMsg, ExecuteMsg, GetTokenResponse, InstantiateMsg, MigrateMsg,
-    QueryMsg, TokenQueryMsg,
+    AssetHolding, BuybackConfig, BuybackResponse, ConfigResponse, Cw20HookMsg, Cw20ReceiveMsg,
+    DividendsResponse, ExecuteMsg, GetTokenResponse, HoldingsResponse, InstantiateMsg,
+    KeeperBounty, KeeperBountyResponse, KeeperReward, LegOutcome, LegPreview, LegStatus,
+    LiquifyOutcome, LiquifyPreviewResponse, LpBurnMode, LpLockResponse, MigrateMsg, PairConfig,
+    QueryMsg, RoutesResponse, SlippageConfig, StatsResponse, TokenExecuteMsg, TokenQueryMsg,
+    TreasuryRatesResponse,
 };
-use choice::asset::{Asset, AssetInfo, PairInfo};
-use choice::pair::QueryMsg as PairQueryMsg;
+use choice::adapter::Dex;
+use choice::asset::{Asset, AssetInfo};
+use choice::router::SwapOperation;
 use cw20_base::ContractError;
 use cw_storage_plus::Item;
+use serde::{Deserialize, Serialize};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "choice:reflection";
@@ -30,11 +35,124 @@ pub const MIN_LIQUIFY_AMT: Item<Uint128> = Item::new("min_liquify_amt"); // mini
 pub const ADMIN: Item<String> = Item::new("admin");
 pub const TOKEN: Item<Addr> = Item::new("token");
 pub const ROUTER: Item<String> = Item::new("router");
+pub const ROUTER_DEX: Item<Dex> = Item::new("router_dex");
+pub const LIQUIDITY_DEX: Item<Dex> = Item::new("liquidity_dex");
+pub const REFLECTION_DEX: Item<Dex> = Item::new("reflection_dex");
 pub const LIQUIDITY_TOKEN: Item<String> = Item::new("liquidity_token");
 pub const LIQUIDITY_PAIR_CONTRACT: Item<String> = Item::new("liquidity_pair_contract");
 pub const REFLECTION_PAIR_CONTRACT: Item<String> = Item::new("reflection_pair_contract");
 pub const LIQUIDITY_PAIR: Item<[AssetInfo; 2]> = Item::new("liquidity_pair");
 pub const REFLECTION_PAIR: Item<[AssetInfo; 2]> = Item::new("reflection_pair");
+pub const STATS: Item<TreasuryStats> = Item::new("stats");
+pub const LIQUIFY_SNAPSHOT: Item<LiquifySnapshot> = Item::new("liquify_snapshot");
+pub const DIVIDENDS_ENABLED: Item<bool> = Item::new("dividends_enabled");
+pub const DIVIDEND_ASSET: Item<AssetInfo> = Item::new("dividend_asset");
+pub const UNCLAIMED_DIVIDENDS: Item<Uint128> = Item::new("unclaimed_dividends");
+pub const SLIPPAGE: Item<SlippageConfig> = Item::new("slippage");
+pub const PENDING_LIQUIDITY: Item<PendingLiquidity> = Item::new("pending_liquidity");
+pub const PENDING_PROVIDE: Item<PendingProvide> = Item::new("pending_provide");
+pub const LAST_LIQUIFY: Item<LiquifyOutcome> = Item::new("last_liquify");
+pub const REFLECTION_ROUTE: Item<Vec<SwapOperation>> = Item::new("reflection_route");
+pub const LIQUIDITY_ROUTE: Item<Vec<SwapOperation>> = Item::new("liquidity_route");
+pub const LP_LOCK: Item<LpLock> = Item::new("lp_lock");
+pub const LP_WITHDRAWN: Item<Uint128> = Item::new("lp_withdrawn");
+pub const LP_BURN: Item<LpBurnMode> = Item::new("lp_burn");
+pub const BUYBACK: Item<BuybackConfig> = Item::new("buyback");
+pub const LAST_BUYBACK: Item<u64> = Item::new("last_buyback");
+pub const PENDING_BUYBACK: Item<PendingBuyback> = Item::new("pending_buyback");
+pub const KEEPER_BOUNTY: Item<KeeperBounty> = Item::new("keeper_bounty");
+pub const LAST_KEEPER_BOUNTY: Item<u64> = Item::new("last_keeper_bounty");
+pub const PENDING_KEEPER_BOUNTY: Item<PendingKeeperBounty> = Item::new("pending_keeper_bounty");
+/// Set while the legs of a liquify run, until one succeeds and the token is told to restart
+/// the split it reports
+pub const PENDING_LIQUIFIED: Item<bool> = Item::new("pending_liquified");
+
+pub const PROVIDE_LIQUIDITY_REPLY_ID: u64 = 1;
+pub const REFLECTION_SWAP_REPLY_ID: u64 = 2;
+pub const LIQUIDITY_SWAP_REPLY_ID: u64 = 3;
+pub const BURN_REPLY_ID: u64 = 4;
+pub const BUYBACK_TRIGGER_REPLY_ID: u64 = 5;
+pub const BUYBACK_SWAP_REPLY_ID: u64 = 6;
+
+/// Shortest time the first LP lock can run before it starts releasing
+pub const MIN_LP_LOCK_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+/// Offer amount routes are simulated with when they are configured
+pub const ROUTE_PROBE_AMOUNT: u128 = 1_000_000;
+
+/// Lifetime counters of the liquify operations
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct TreasuryStats {
+    pub total_liquified: Uint128,
+    pub total_lp_minted: Uint128,
+    pub total_lp_burned: Uint128,
+    pub total_reward_acquired: Uint128,
+    pub liquify_runs: u64,
+    pub total_buyback_spent: Uint128,
+    pub total_buyback_burned: Uint128,
+    pub total_keeper_bounties: Uint128,
+}
+
+/// Balances taken before a liquify leg executes, used to measure what the leg produced
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct LiquifySnapshot {
+    pub lp_balance: Uint128,
+    pub reward_asset: Option<AssetInfo>,
+    pub reward_balance: Uint128,
+}
+
+/// Time lock on the LP tokens held by the treasury
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LpLock {
+    pub unlock_time: u64,
+    pub release_duration: u64,
+}
+
+impl LpLock {
+    /// Share of the LP tokens released at block time `now`
+    pub fn released(&self, now: u64) -> Decimal {
+        if now < self.unlock_time {
+            Decimal::zero()
+        } else if now >= self.unlock_time.saturating_add(self.release_duration) {
+            Decimal::one()
+        } else {
+            Decimal::from_ratio(now - self.unlock_time, self.release_duration)
+        }
+    }
+}
+
+/// Liquidity leg waiting for its swap to complete
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingLiquidity {
+    /// Reflection token kept to pair with the swap proceeds
+    pub token_amount: Uint128,
+    pub quote_asset: AssetInfo,
+    /// Quote asset balance before the swap
+    pub quote_balance: Uint128,
+}
+
+/// CW20 allowances granted to the liquidity pair for a provide, revoked when it fails
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingProvide {
+    pub spender: String,
+    pub allowances: Vec<Asset>,
+}
+
+/// Buyback waiting for its swap to complete
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingBuyback {
+    /// Quote asset offered to the pair
+    pub offer_amount: Uint128,
+    /// Reflection token balance before the swap
+    pub token_balance: Uint128,
+}
+
+/// Keeper bounty set aside by a liquify, paid by the reply of its first leg that succeeds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingKeeperBounty {
+    pub keeper: Addr,
+    pub amount: Uint128,
+}
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -69,17 +187,40 @@ pub fn execute(
         ExecuteMsg::SetReflectionPair {
             asset_infos,
             pair_contract,
-        } => set_reflection_pair(deps, env, info, asset_infos, pair_contract),
+            dex,
+        } => set_reflection_pair(deps, env, info, asset_infos, pair_contract, dex),
         ExecuteMsg::SetLiquidityPair {
             asset_infos,
             pair_contract,
-        } => set_liquidity_pair(deps, env, info, asset_infos, pair_contract),
+            dex,
+        } => set_liquidity_pair(deps, env, info, asset_infos, pair_contract, dex),
+        ExecuteMsg::SetRouter { router, dex } => set_router(deps, info, router, dex),
+        ExecuteMsg::SetLpLock {
+            unlock_time,
+            release_duration,
+        } => set_lp_lock(deps, env, info, unlock_time, release_duration),
+        ExecuteMsg::WithdrawLp { amount } => withdraw_lp(deps, env, info, amount),
+        ExecuteMsg::SetLpBurn { mode } => set_lp_burn(deps, info, mode),
+        ExecuteMsg::SetBuyback { config } => set_buyback(deps, info, config),
+        ExecuteMsg::Buyback {} => buyback(deps, env),
         ExecuteMsg::SetMinLiquify { min_liquify_amt } => {
             set_min_liquify_amt(deps, env, info, min_liquify_amt)
         }
-        ExecuteMsg::Liquify {} => liquify_treasury(&deps.querier, env, deps.storage),
+        ExecuteMsg::Liquify {} => {
+            liquify_treasury(&deps.querier, env, deps.storage, Some(info.sender))
+        }
+        ExecuteMsg::SetKeeperBounty { bounty } => set_keeper_bounty(deps, info, bounty),
         ExecuteMsg::WithdrawToken { asset } => withdraw_token(deps, env, info, asset),
         ExecuteMsg::TransferAdmin { new_admin } => transfer_admin(deps, info, new_admin),
+        ExecuteMsg::SetDividends { enabled } => set_dividends(deps, info, enabled),
+        ExecuteMsg::PayDividend { recipient, amount } => {
+            pay_dividend(deps, info, recipient, amount)
+        }
+        ExecuteMsg::SetSlippage { config } => set_slippage(deps, info, config),
+        ExecuteMsg::SetReflectionRoute { operations } => {
+            set_reflection_route(deps, info, operations)
+        }
+        ExecuteMsg::SetLiquidityRoute { operations } => set_liquidity_route(deps, info, operations),
     }
 }
 
@@ -104,9 +245,145 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             to_json_binary(&query_balance(&deps.querier, token, env.contract.address)?)
         }
         QueryMsg::GetToken {} => to_json_binary(&query_token(deps.storage)?),
+        QueryMsg::Config {} => to_json_binary(&query_config(deps.storage)?),
+        QueryMsg::Holdings {} => to_json_binary(&query_holdings(deps, &env)?),
+        QueryMsg::Stats {} => to_json_binary(&query_stats(deps.storage)?),
+        QueryMsg::Dividends {} => to_json_binary(&query_dividends(deps.storage)?),
+        QueryMsg::LpLock {} => to_json_binary(&query_lp_lock(deps, &env)?),
+        QueryMsg::LpBurn {} => to_json_binary(&LP_BURN.may_load(deps.storage)?),
+        QueryMsg::Buyback {} => to_json_binary(&query_buyback(deps, &env)?),
+        QueryMsg::KeeperBounty {} => to_json_binary(&query_keeper_bounty(deps.storage)?),
+        QueryMsg::Routes {} => to_json_binary(&RoutesResponse {
+            reflection: REFLECTION_ROUTE.may_load(deps.storage)?,
+            liquidity: LIQUIDITY_ROUTE.may_load(deps.storage)?,
+        }),
+        QueryMsg::LiquifyPreview { keeper } => {
+            to_json_binary(&query_liquify_preview(deps, &env, keeper)?)
+        }
+        QueryMsg::LastLiquify {} => to_json_binary(&LAST_LIQUIFY.may_load(deps.storage)?),
+        QueryMsg::Slippage {} => {
+            to_json_binary(&SLIPPAGE.may_load(deps.storage)?.unwrap_or_default())
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, reply: Reply) -> Result<Response, ContractError> {
+    match reply.id {
+        BUYBACK_TRIGGER_REPLY_ID => return record_buyback_failure(deps, env, reply.result),
+        BUYBACK_SWAP_REPLY_ID => return burn_bought_back(deps, env, reply.result),
+        _ => {}
+    }
+    // Legs reply on error too, so a failed leg is recorded instead of reverting the others
+    if let SubMsgResult::Err(error) = reply.result {
+        return record_leg_failure(deps, env, reply.id, error);
+    }
+    match reply.id {
+        PROVIDE_LIQUIDITY_REPLY_ID => record_lp_minted(deps, env),
+        REFLECTION_SWAP_REPLY_ID => record_reward_acquired(deps, env),
+        LIQUIDITY_SWAP_REPLY_ID => provide_swapped_liquidity(deps, env),
+        BURN_REPLY_ID => {
+            set_leg_status(deps.storage, BURN_REPLY_ID, LegStatus::Succeeded {})?;
+            Ok(settle_leg_success(deps.storage, &env, Response::default())?)
+        }
+        _id => Err(ContractError::Unauthorized {}),
+    }
+}
+
+/// The leg of the last liquify a reply belongs to
+fn leg_of(outcome: &mut LiquifyOutcome, reply_id: u64) -> Option<&mut LegOutcome> {
+    match reply_id {
+        LIQUIDITY_SWAP_REPLY_ID | PROVIDE_LIQUIDITY_REPLY_ID => Some(&mut outcome.liquidity),
+        REFLECTION_SWAP_REPLY_ID => Some(&mut outcome.reflection),
+        BURN_REPLY_ID => Some(&mut outcome.burn),
+        _ => None,
     }
 }
 
+/// Sets the status of a leg of the last liquify, counting the leg's amount as liquified
+/// when it succeeded
+fn set_leg_status(storage: &mut dyn Storage, reply_id: u64, status: LegStatus) -> StdResult<()> {
+    let Some(mut outcome) = LAST_LIQUIFY.may_load(storage)? else {
+        return Ok(());
+    };
+    if let Some(leg) = leg_of(&mut outcome, reply_id) {
+        if status == (LegStatus::Succeeded {}) {
+            let mut stats = STATS.may_load(storage)?.unwrap_or_default();
+            stats.total_liquified += leg.amount;
+            STATS.save(storage, &stats)?;
+        }
+        leg.status = status;
+    }
+    LAST_LIQUIFY.save(storage, &outcome)
+}
+
+/// Records a failed leg. Its submessage was reverted, so the funds it was given stay in the
+/// treasury, except the swap proceeds of a failed liquidity provision which are kept as well.
+/// The allowances granted for a failed provision are revoked.
+pub fn record_leg_failure(
+    deps: DepsMut,
+    env: Env,
+    reply_id: u64,
+    error: String,
+) -> Result<Response, ContractError> {
+    let mut response = Response::new();
+    match reply_id {
+        LIQUIDITY_SWAP_REPLY_ID => PENDING_LIQUIDITY.remove(deps.storage),
+        PROVIDE_LIQUIDITY_REPLY_ID => {
+            if let Some(pending) = PENDING_PROVIDE.may_load(deps.storage)? {
+                PENDING_PROVIDE.remove(deps.storage);
+                for allowance in pending.allowances {
+                    if let AssetInfo::Token { contract_addr } = allowance.info {
+                        response = response.add_message(WasmMsg::Execute {
+                            contract_addr,
+                            msg: to_json_binary(&Cw20ExecuteMsg::DecreaseAllowance {
+                                spender: pending.spender.clone(),
+                                amount: allowance.amount,
+                                expires: None,
+                            })?,
+                            funds: vec![],
+                        });
+                    }
+                }
+            }
+
+            // The kept swap proceeds are not rewards of the reflection leg
+            let mut snapshot = LIQUIFY_SNAPSHOT.load(deps.storage)?;
+            if let Some(reward_asset) = &snapshot.reward_asset {
+                snapshot.reward_balance =
+                    query_asset_balance(&deps.querier, reward_asset, env.contract.address)?;
+            }
+            LIQUIFY_SNAPSHOT.save(deps.storage, &snapshot)?;
+        }
+        REFLECTION_SWAP_REPLY_ID | BURN_REPLY_ID => {}
+        _id => return Err(ContractError::Unauthorized {}),
+    }
+    set_leg_status(
+        deps.storage,
+        reply_id,
+        LegStatus::Failed {
+            error: error.clone(),
+        },
+    )?;
+    Ok(response
+        .add_attribute("leg_failed", reply_id.to_string())
+        .add_attribute("error", error))
+}
+
+pub fn query_stats(storage: &dyn Storage) -> StdResult<StatsResponse> {
+    let stats = STATS.may_load(storage)?.unwrap_or_default();
+    Ok(StatsResponse {
+        total_liquified: stats.total_liquified,
+        total_lp_minted: stats.total_lp_minted,
+        total_lp_burned: stats.total_lp_burned,
+        total_reward_acquired: stats.total_reward_acquired,
+        liquify_runs: stats.liquify_runs,
+        total_buyback_spent: stats.total_buyback_spent,
+        total_buyback_burned: stats.total_buyback_burned,
+        total_keeper_bounties: stats.total_keeper_bounties,
+    })
+}
+
 pub fn query_token(storage: &dyn Storage) -> StdResult<GetTokenResponse> {
     let token_addr = TOKEN.load(storage)?;
     Ok(GetTokenResponse {
@@ -114,6 +391,86 @@ pub fn query_token(storage: &dyn Storage) -> StdResult<GetTokenResponse> {
     })
 }
 
+pub fn query_config(storage: &dyn Storage) -> StdResult<ConfigResponse> {
+    let token = TOKEN.load(storage)?;
+    let liquidity_pair = match (
+        LIQUIDITY_PAIR.may_load(storage)?,
+        LIQUIDITY_PAIR_CONTRACT.may_load(storage)?,
+    ) {
+        (Some(asset_infos), Some(contract)) => Some(PairConfig {
+            asset_infos,
+            contract,
+            dex: LIQUIDITY_DEX.may_load(storage)?.unwrap_or_default(),
+        }),
+        _ => None,
+    };
+    let reflection_pair = match (
+        REFLECTION_PAIR.may_load(storage)?,
+        REFLECTION_PAIR_CONTRACT.may_load(storage)?,
+    ) {
+        (Some(asset_infos), Some(contract)) => Some(PairConfig {
+            asset_infos,
+            contract,
+            dex: REFLECTION_DEX.may_load(storage)?.unwrap_or_default(),
+        }),
+        _ => None,
+    };
+    let reward_asset = match &reflection_pair {
+        Some(pair) => Some(reward_asset(storage, &token, &pair.asset_infos)?),
+        None => None,
+    };
+    Ok(ConfigResponse {
+        admin: ADMIN.load(storage)?,
+        token: token.to_string(),
+        router: ROUTER.load(storage)?,
+        router_dex: ROUTER_DEX.may_load(storage)?.unwrap_or_default(),
+        liquidity_pair,
+        reflection_pair,
+        liquidity_token: LIQUIDITY_TOKEN.may_load(storage)?,
+        reward_asset,
+        min_liquify_amt: MIN_LIQUIFY_AMT.may_load(storage)?.unwrap_or_default(),
+        slippage: SLIPPAGE.may_load(storage)?.unwrap_or_default(),
+        reflection_route: REFLECTION_ROUTE.may_load(storage)?,
+        liquidity_route: LIQUIDITY_ROUTE.may_load(storage)?,
+    })
+}
+
+/// Balances of the reflection token, LP token, reward asset and quote asset
+pub fn query_holdings(deps: Deps, env: &Env) -> StdResult<HoldingsResponse> {
+    let holding = |asset: AssetInfo| -> StdResult<AssetHolding> {
+        Ok(AssetHolding {
+            balance: query_asset_balance(&deps.querier, &asset, env.contract.address.clone())?,
+            reserved: reserved_amount(deps.storage, &asset)?,
+            asset,
+        })
+    };
+    let config = query_config(deps.storage)?;
+    let lp = match LIQUIDITY_TOKEN.may_load(deps.storage)? {
+        Some(_) => {
+            let lock = query_lp_lock(deps, env)?;
+            Some(AssetHolding {
+                asset: AssetInfo::Token {
+                    contract_addr: lock.lp_token.unwrap_or_default(),
+                },
+                balance: lock.balance,
+                reserved: lock.locked,
+            })
+        }
+        None => None,
+    };
+    Ok(HoldingsResponse {
+        token: holding(AssetInfo::Token {
+            contract_addr: config.token,
+        })?,
+        lp,
+        reward: config.reward_asset.map(holding).transpose()?,
+        quote: config
+            .liquidity_pair
+            .map(|pair| holding(pair.asset_infos[1].clone()))
+            .transpose()?,
+    })
+}
+
 pub fn receive_cw20(
     querier: &QuerierWrapper,
     storage: &mut dyn Storage,
@@ -131,7 +488,7 @@ pub fn receive_cw20(
                 return Err(ContractError::Unauthorized {});
             }
 
-            liquify_treasury(querier, env.clone(), storage)
+            liquify_treasury(querier, env.clone(), storage, None)
         }
         Err(_) => Err(ContractError::Unauthorized {}),
     }
@@ -141,29 +498,25 @@ pub fn receive_cw20(
 /// 1. Liquify reflection token into LP tokens
 /// 2. Reflect reflection token into target token to be sent into fee collector wallet
 /// 3. Burn a portion of reflection token
+///
+/// Each leg is a submessage replying on error, a failing leg does not revert the others.
+/// The keeper bounty of the `keeper` calling it is set aside before the split, and paid once
+/// a leg succeeds, which is also when the token is told the treasury liquified.
 pub fn liquify_treasury(
     querier: &QuerierWrapper,
     env: Env,
     storage: &mut dyn Storage,
+    keeper: Option<Addr>,
 ) -> Result<Response, ContractError> {
-    let liquidity_pair = match LIQUIDITY_PAIR.may_load(storage)? {
-        Some(pair) => pair,
-        None => return Ok(Response::default()), // Exit early
-    };
-    let liquidity_pair_contract = match LIQUIDITY_PAIR_CONTRACT.may_load(storage)? {
-        Some(contract) => contract,
-        None => return Ok(Response::default()), // Exit early
-    };
-    let reflection_pair = match REFLECTION_PAIR.may_load(storage)? {
-        Some(pair) => pair,
-        None => return Ok(Response::default()), // Exit early
-    };
-    let router = match ROUTER.may_load(storage)? {
-        Some(r) => r,
-        None => return Ok(Response::default()), // Exit early
-    };
+    if !liquify_configured(storage)? {
+        return Ok(Response::default()); // Exit early
+    }
 
     let querier = *querier;
+    let buyback = buyback_trigger(storage, &env)?;
+    // A bounty or reset left by a run whose legs all failed is not owed anymore
+    PENDING_KEEPER_BOUNTY.remove(storage);
+    PENDING_LIQUIFIED.remove(storage);
 
     // let admin = ADMIN.may_load(storage)?.unwrap_or_default();
     let token = TOKEN.load(storage)?;
@@ -175,191 +528,1078 @@ pub fn liquify_treasury(
 
     // Short circuit if there's not enough contract balance to liquify
     if contract_balance < min_liquify_amt {
-        return Ok(Response::default());
+        return Ok(Response::new().add_submessages(buyback));
     }
 
-    // Loads all the tax rates from the modified CW20 token
-    let (_tax_rate, reflection_rate, burn_rate, _transfer_rate): (
-        Decimal,
-        Decimal,
-        Decimal,
-        Decimal,
-    ) = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: token.to_string(),
-        msg: to_json_binary(&TokenQueryMsg::QueryRates {})?,
-    }))?;
-
-    let mut messages: Vec<WasmMsg> = vec![];
-
-    let reflect_amt = contract_balance.mul_floor(reflection_rate);
-    let burn_amt = contract_balance.mul_floor(burn_rate);
-
-    let liquidity_amt = contract_balance.sub(reflect_amt).sub(burn_amt);
+    // The token's own trigger is not a keeper
+    let bounty = match keeper.filter(|keeper| *keeper != token) {
+        Some(keeper) => Some(PendingKeeperBounty {
+            amount: keeper_bounty_amount(storage, &env, contract_balance)?,
+            keeper,
+        })
+        .filter(|bounty| !bounty.amount.is_zero()),
+        None => None,
+    };
+    let bounty_amount = bounty
+        .as_ref()
+        .map(|bounty| bounty.amount)
+        .unwrap_or_default();
+
+    let plan = plan_liquify(&querier, storage, &env, contract_balance - bounty_amount)?;
+    let liquidity_pair = LIQUIDITY_PAIR.load(storage)?;
+    let reflection_pair = REFLECTION_PAIR.load(storage)?;
+
+    let mut messages: Vec<SubMsg> = vec![];
+
+    let mut outcome = LiquifyOutcome {
+        height: env.block.height,
+        liquidity: LegOutcome::new(plan.liquidity_amt),
+        reflection: LegOutcome::new(plan.reflect_amt),
+        burn: LegOutcome::new(plan.burn_amt),
+    };
     // Taxes - 100000
     // Reflection - 50000
     // Burn - 10000
     // Liq amt - 40000
-    if liquidity_amt > Uint128::zero() {
-        // Swaps half of reflection token into INJ
-        let swap_amount = liquidity_amt.div(Uint128::from(2u128));
-        // Increases allowance of reflection token to liquidity pair contract (allows adding liquidity)
-        messages.push(WasmMsg::Execute {
-            contract_addr: token.to_string(),
-            msg: to_json_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
-                spender: liquidity_pair_contract.clone(),
-                amount: liquidity_amt.sub(swap_amount),
-                expires: None,
-            })?,
-            funds: vec![],
-        });
+    match plan.liquidity_swap {
+        None => {}
+        Some(Err(err)) => {
+            outcome.liquidity.status = LegStatus::Failed {
+                error: err.to_string(),
+            }
+        }
+        Some(Ok(swap)) => {
+            // Remember the remaining half and the INJ balance the swap starts from
+            PENDING_LIQUIDITY.save(
+                storage,
+                &PendingLiquidity {
+                    token_amount: plan.liquidity_amt.sub(swap.amount),
+                    quote_asset: liquidity_pair[1].clone(),
+                    quote_balance: query_asset_balance(
+                        &querier,
+                        &liquidity_pair[1],
+                        env.contract.address.clone(),
+                    )?,
+                },
+            )?;
+
+            // We formulate a swap message to swap reflection token into INJ, the reply provides
+            // liquidity with what the swap actually returned
+            messages.push(SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: token.to_string(),
+                    msg: to_json_binary(&cw20::Cw20ExecuteMsg::Send {
+                        contract: swap.contract,
+                        amount: swap.amount,
+                        msg: swap.msg,
+                    })?,
+                    funds: vec![],
+                },
+                LIQUIDITY_SWAP_REPLY_ID,
+            ));
+        }
+    }
+
+    match plan.reflection_swap {
+        None => {}
+        Some(Err(err)) => {
+            outcome.reflection.status = LegStatus::Failed {
+                error: err.to_string(),
+            }
+        }
+        Some(Ok(swap)) => {
+            // The reward token is sent back into the treasury
+            messages.push(SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: token.to_string(),
+                    msg: to_json_binary(&cw20::Cw20ExecuteMsg::Send {
+                        contract: swap.contract,
+                        amount: swap.amount,
+                        msg: swap.msg,
+                    })?,
+                    funds: vec![],
+                },
+                REFLECTION_SWAP_REPLY_ID,
+            ));
+        }
+    }
 
-        // Simulates swapping of half of reflection token into INJ
-        let simulation = simulate(
-            &querier,
-            liquidity_pair_contract.clone(),
-            &Asset {
-                amount: swap_amount,
-                info: liquidity_pair[0].clone(),
+    if plan.burn_amt > Uint128::zero() {
+        // Burn
+        messages.push(SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                msg: to_json_binary(&cw20::Cw20ExecuteMsg::Burn {
+                    amount: plan.burn_amt,
+                })?,
+                funds: vec![],
+            },
+            BURN_REPLY_ID,
+        ));
+    }
+
+    if !messages.is_empty() {
+        // Remember the balances the legs start from, so the replies can measure what they produced
+        let lp_balance = match LIQUIDITY_TOKEN.may_load(storage)? {
+            Some(lp_token) if plan.liquidity_amt > Uint128::zero() => query_balance(
+                &querier,
+                Addr::unchecked(lp_token),
+                env.contract.address.clone(),
+            )?,
+            _ => Uint128::zero(),
+        };
+        let reward_asset = reward_asset(storage, &token, &reflection_pair)?;
+        let reward_balance =
+            query_asset_balance(&querier, &reward_asset, env.contract.address.clone())?;
+        LIQUIFY_SNAPSHOT.save(
+            storage,
+            &LiquifySnapshot {
+                lp_balance,
+                reward_asset: Some(reward_asset),
+                reward_balance,
             },
         )?;
-        // We formulate a swap message to swap reflection token into INJ
-        messages.push(WasmMsg::Execute {
-            contract_addr: token.to_string(),
-            msg: to_json_binary(&cw20::Cw20ExecuteMsg::Send {
-                contract: liquidity_pair_contract.to_string(),
-                amount: swap_amount,
-                msg: to_json_binary(&choice::pair::Cw20HookMsg::Swap {
-                    belief_price: None,
-                    max_spread: None,
-                    to: None,
-                    deadline: None,
-                })?,
+
+        let mut stats = STATS.may_load(storage)?.unwrap_or_default();
+        stats.liquify_runs += 1;
+        STATS.save(storage, &stats)?;
+
+        // Bounties are only paid for runs that dispatched a leg, by the leg replies
+        if let Some(bounty) = &bounty {
+            PENDING_KEEPER_BOUNTY.save(storage, bounty)?;
+        }
+        PENDING_LIQUIFIED.save(storage, &true)?;
+    }
+    let skipped = LegStatus::Skipped {};
+    if [&outcome.liquidity, &outcome.reflection, &outcome.burn]
+        .iter()
+        .any(|leg| leg.status != skipped)
+    {
+        LAST_LIQUIFY.save(storage, &outcome)?;
+    }
+
+    // The buyback runs after the legs, so their balance deltas are not mixed with it
+    let res = Response::new()
+        .add_submessages(messages)
+        .add_submessages(buyback);
+
+    Ok(res)
+}
+
+/// Settles what the running liquify owes on the reply of its first leg that succeeded
+fn settle_leg_success(
+    storage: &mut dyn Storage,
+    env: &Env,
+    response: Response,
+) -> StdResult<Response> {
+    let response = report_liquified(storage, response)?;
+    pay_keeper_bounty(storage, env, response)
+}
+
+/// Tells the token to restart the split it reports from the taxes received after the running
+/// liquify, so a run whose legs all failed keeps the split of the balance it left
+fn report_liquified(storage: &mut dyn Storage, response: Response) -> StdResult<Response> {
+    if !PENDING_LIQUIFIED.may_load(storage)?.unwrap_or(false) {
+        return Ok(response);
+    }
+    PENDING_LIQUIFIED.remove(storage);
+
+    Ok(response.add_message(WasmMsg::Execute {
+        contract_addr: TOKEN.load(storage)?.to_string(),
+        msg: to_json_binary(&TokenExecuteMsg::TreasuryLiquified {})?,
+        funds: vec![],
+    }))
+}
+
+/// Pays the bounty set aside by the running liquify, on the reply of its first leg that
+/// succeeded
+fn pay_keeper_bounty(
+    storage: &mut dyn Storage,
+    env: &Env,
+    response: Response,
+) -> StdResult<Response> {
+    let Some(bounty) = PENDING_KEEPER_BOUNTY.may_load(storage)? else {
+        return Ok(response);
+    };
+    PENDING_KEEPER_BOUNTY.remove(storage);
+    LAST_KEEPER_BOUNTY.save(storage, &env.block.time.seconds())?;
+    let mut stats = STATS.may_load(storage)?.unwrap_or_default();
+    stats.total_keeper_bounties += bounty.amount;
+    STATS.save(storage, &stats)?;
+
+    Ok(response
+        .add_message(WasmMsg::Execute {
+            contract_addr: TOKEN.load(storage)?.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: bounty.keeper.to_string(),
+                amount: bounty.amount,
             })?,
             funds: vec![],
-        });
+        })
+        .add_attribute("keeper", bounty.keeper)
+        .add_attribute("keeper_bounty", bounty.amount))
+}
 
-        // Formulate variable to allow us to add liquidity to the pool
-        let assets: [Asset; 2] = [
-            Asset {
-                amount: liquidity_amt.sub(swap_amount), // add remaining amount of reflection token as liquidity
-                info: liquidity_pair[0].clone(),        // reflection token
-            },
-            Asset {
-                amount: simulation.return_amount, // add simulated INJ return amount to be added as liquidity
-                info: liquidity_pair[1].clone(),  // INJ
-            },
-        ];
-
-        // We formulate a ProvideLiquidity message to add reflection token liquidity to the pool
-        match reflection_pair[1].clone() {
-            AssetInfo::NativeToken { denom } => {
-                // If the asset is a native token, we provide liquidity via a denom message
-                messages.push(WasmMsg::Execute {
-                    contract_addr: liquidity_pair_contract.to_string(),
-                    msg: to_json_binary(&choice::pair::ExecuteMsg::ProvideLiquidity {
-                        assets,
-                        receiver: None,
-                        deadline: None,
-                        slippage_tolerance: None,
-                    })?,
-                    funds: vec![coin(simulation.return_amount.u128(), denom)],
-                });
-            }
-            AssetInfo::Token { contract_addr } => {
-                // If asset is a CW20, we provide liquidity via increase allowance message
-                messages.push(WasmMsg::Execute {
-                    contract_addr,
-                    msg: to_json_binary(&Cw20ExecuteMsg::IncreaseAllowance {
-                        spender: liquidity_pair_contract.to_string(),
-                        amount: simulation.return_amount,
-                        expires: None,
-                    })?,
-                    funds: vec![],
-                });
-                messages.push(WasmMsg::Execute {
-                    contract_addr: liquidity_pair_contract.to_string(),
-                    msg: to_json_binary(&choice::pair::ExecuteMsg::ProvideLiquidity {
-                        assets,
-                        receiver: None,
-                        deadline: None,
-                        slippage_tolerance: None,
-                    })?,
-                    funds: vec![],
-                });
+/// Bounty a keeper liquifying `balance` is paid, zero within the interval after the last one
+fn keeper_bounty_amount(storage: &dyn Storage, env: &Env, balance: Uint128) -> StdResult<Uint128> {
+    let Some(bounty) = KEEPER_BOUNTY.may_load(storage)? else {
+        return Ok(Uint128::zero());
+    };
+    if let Some(last) = LAST_KEEPER_BOUNTY.may_load(storage)? {
+        if last.saturating_add(bounty.min_interval_seconds) > env.block.time.seconds() {
+            return Ok(Uint128::zero());
+        }
+    }
+    Ok(match bounty.reward {
+        KeeperReward::Share { rate } => balance.mul_floor(rate),
+        KeeperReward::Fixed { amount } => amount.min(balance),
+    })
+}
+
+/// Sets the bounty paid to callers of liquify
+pub fn set_keeper_bounty(
+    deps: DepsMut,
+    info: MessageInfo,
+    bounty: Option<KeeperBounty>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    match &bounty {
+        Some(bounty) => {
+            if let KeeperReward::Share { rate } = bounty.reward {
+                if rate > Decimal::one() {
+                    return Err(ContractError::Std(StdError::generic_err(
+                        "Keeper share must be <= 1",
+                    )));
+                }
             }
+            KEEPER_BOUNTY.save(deps.storage, bounty)?;
+        }
+        None => KEEPER_BOUNTY.remove(deps.storage),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_keeper_bounty")
+        .add_attribute("enabled", bounty.is_some().to_string()))
+}
+
+pub fn query_keeper_bounty(storage: &dyn Storage) -> StdResult<KeeperBountyResponse> {
+    let bounty = KEEPER_BOUNTY.may_load(storage)?;
+    let last_paid = LAST_KEEPER_BOUNTY.may_load(storage)?;
+    let next_paid = bounty.as_ref().map(|bounty| match last_paid {
+        Some(last) => last.saturating_add(bounty.min_interval_seconds),
+        None => 0,
+    });
+    Ok(KeeperBountyResponse {
+        bounty,
+        last_paid,
+        next_paid,
+    })
+}
+
+/// Liquify needs both pairs and the router
+fn liquify_configured(storage: &dyn Storage) -> StdResult<bool> {
+    Ok(LIQUIDITY_PAIR.may_load(storage)?.is_some()
+        && LIQUIDITY_PAIR_CONTRACT.may_load(storage)?.is_some()
+        && REFLECTION_PAIR.may_load(storage)?.is_some()
+        && ROUTER.may_load(storage)?.is_some())
+}
+
+/// What liquify does with a balance of the reflection token
+pub struct LiquifyPlan {
+    pub liquidity_amt: Uint128,
+    pub reflect_amt: Uint128,
+    pub burn_amt: Uint128,
+    /// Swap of half the liquidity amount into the quote asset, not set when there is nothing
+    /// to swap and an error when it cannot be simulated
+    pub liquidity_swap: Option<StdResult<PlannedSwap>>,
+    /// Swap of the reflection amount into the reward asset
+    pub reflection_swap: Option<StdResult<PlannedSwap>>,
+}
+
+/// Reflection token sent to a pair or router with its swap hook
+pub struct PlannedSwap {
+    pub contract: String,
+    pub amount: Uint128,
+    pub msg: Binary,
+    /// Amount the simulation returned
+    pub expected: Uint128,
+}
+
+/// Splits `contract_balance` between the legs from the token's treasury rates and simulates
+/// the swaps, shared by liquify and its preview. Both pairs and the router must be set.
+pub fn plan_liquify(
+    querier: &QuerierWrapper,
+    storage: &dyn Storage,
+    env: &Env,
+    contract_balance: Uint128,
+) -> StdResult<LiquifyPlan> {
+    let token = TOKEN.load(storage)?;
+    let liquidity_pair = LIQUIDITY_PAIR.load(storage)?;
+    let liquidity_pair_contract = LIQUIDITY_PAIR_CONTRACT.load(storage)?;
+    let reflection_pair = REFLECTION_PAIR.load(storage)?;
+    let router = ROUTER.load(storage)?;
+
+    // Loads the split of the treasury balance from the modified CW20 token
+    let TreasuryRatesResponse {
+        reflection_rate,
+        burn_rate,
+    } = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: token.to_string(),
+        msg: to_json_binary(&TokenQueryMsg::TreasuryRates {})?,
+    }))?;
+
+    // The simulations run in this transaction, so the bounds only limit the price impact of
+    // each leg, they cannot see trades that came before
+    let slippage = SLIPPAGE.may_load(storage)?.unwrap_or_default();
+    let deadline = Some(env.block.time.seconds() + slippage.deadline_seconds);
+    let router_dex = ROUTER_DEX.may_load(storage)?.unwrap_or_default();
+    let liquidity_dex = LIQUIDITY_DEX.may_load(storage)?.unwrap_or_default();
+
+    let reflect_amt = contract_balance.mul_floor(reflection_rate);
+    let burn_amt = contract_balance.mul_floor(burn_rate);
+    let liquidity_amt = contract_balance.sub(reflect_amt).sub(burn_amt);
+
+    let mut liquidity_swap = None;
+    if liquidity_amt > Uint128::zero() {
+        // Swaps half of reflection token into INJ
+        let swap_amount = liquidity_amt.div(Uint128::from(2u128));
+
+        // The swap follows the admin's route when one is set, otherwise it is made in the pair
+        let swap = match LIQUIDITY_ROUTE.may_load(storage)? {
+            Some(operations) => router_swap_hook(
+                querier,
+                router_dex,
+                &router,
+                operations,
+                swap_amount,
+                &slippage,
+                deadline,
+            )
+            .map(|(msg, expected)| (router.clone(), msg, expected)),
+            None => pair_swap_hook(
+                querier,
+                liquidity_dex,
+                &liquidity_pair_contract,
+                &liquidity_pair[0],
+                swap_amount,
+                &slippage,
+                deadline,
+            )
+            .map(|(msg, expected)| (liquidity_pair_contract.clone(), msg, expected)),
         };
+        liquidity_swap = Some(swap.map(|(contract, msg, expected)| PlannedSwap {
+            contract,
+            amount: swap_amount,
+            msg,
+            expected,
+        }));
     }
 
+    let mut reflection_swap = None;
     if reflect_amt > Uint128::zero() {
-        let self_token_info = AssetInfo::Token {
-            contract_addr: token.to_string(),
+        let operations = match REFLECTION_ROUTE.may_load(storage)? {
+            Some(operations) => operations,
+            None => reflection_operations(&token, &reflection_pair),
         };
-        // 1. Define the first swap operation, which is always required.
-        // This swaps your reflection token into the intermediate asset (e.g., INJ).
-        let first_op = choice::router::SwapOperation::Choice {
-            offer_asset_info: choice::asset::AssetInfo::Token {
-                contract_addr: token.to_string(),
+        reflection_swap = Some(
+            router_swap_hook(
+                querier,
+                router_dex,
+                &router,
+                operations,
+                reflect_amt,
+                &slippage,
+                deadline,
+            )
+            .map(|(msg, expected)| PlannedSwap {
+                contract: router.clone(),
+                amount: reflect_amt,
+                msg,
+                expected,
+            }),
+        );
+    }
+
+    Ok(LiquifyPlan {
+        liquidity_amt,
+        reflect_amt,
+        burn_amt,
+        liquidity_swap,
+        reflection_swap,
+    })
+}
+
+/// Runs the liquify computation on the treasury's balance without executing it, as called by
+/// `keeper`. The keeper bounty is only set aside for a keeper other than the token.
+pub fn query_liquify_preview(
+    deps: Deps,
+    env: &Env,
+    keeper: Option<String>,
+) -> StdResult<Option<LiquifyPreviewResponse>> {
+    if !liquify_configured(deps.storage)? {
+        return Ok(None);
+    }
+    let token = TOKEN.load(deps.storage)?;
+    let balance = query_balance(&deps.querier, token.clone(), env.contract.address.clone())?;
+    let min_liquify_amt = MIN_LIQUIFY_AMT.may_load(deps.storage)?.unwrap_or_default();
+    let keeper_bounty = match keeper {
+        Some(keeper) if deps.api.addr_validate(&keeper)? != token => {
+            keeper_bounty_amount(deps.storage, env, balance)?
+        }
+        _ => Uint128::zero(),
+    };
+    let plan = plan_liquify(&deps.querier, deps.storage, env, balance - keeper_bounty)?;
+
+    let mut liquidity = LegPreview::new(plan.liquidity_amt);
+    let mut expected_lp = None;
+    match &plan.liquidity_swap {
+        None => {}
+        Some(Err(err)) => liquidity.error = Some(err.to_string()),
+        Some(Ok(swap)) => {
+            liquidity.expected = Some(swap.expected);
+            expected_lp = expected_lp_minted(
+                deps,
+                plan.liquidity_amt.sub(swap.amount),
+                swap,
+                LIQUIDITY_ROUTE.may_load(deps.storage)?.is_none(),
+            )
+            .ok()
+            .flatten();
+        }
+    }
+    let mut reflection = LegPreview::new(plan.reflect_amt);
+    match &plan.reflection_swap {
+        None => {}
+        Some(Err(err)) => reflection.error = Some(err.to_string()),
+        Some(Ok(swap)) => reflection.expected = Some(swap.expected),
+    }
+
+    Ok(Some(LiquifyPreviewResponse {
+        balance,
+        min_liquify_amt,
+        blocked: balance < min_liquify_amt,
+        liquidity,
+        expected_lp,
+        reflection,
+        burn_amount: plan.burn_amt,
+        keeper_bounty,
+    }))
+}
+
+/// LP tokens minted by providing `token_amount` with the expected swap proceeds, from the
+/// pool's reserves after the swap when it is made in the pair. `None` for an empty pool.
+fn expected_lp_minted(
+    deps: Deps,
+    token_amount: Uint128,
+    swap: &PlannedSwap,
+    swapped_in_pair: bool,
+) -> StdResult<Option<Uint128>> {
+    let liquidity_pair = LIQUIDITY_PAIR.load(deps.storage)?;
+    let pool = LIQUIDITY_DEX
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .query_pool(&deps.querier, &LIQUIDITY_PAIR_CONTRACT.load(deps.storage)?)?;
+    let reserve = |info: &AssetInfo| {
+        pool.assets
+            .iter()
+            .find(|asset| asset.info.equal(info))
+            .map(|asset| asset.amount)
+            .unwrap_or_default()
+    };
+    let mut token_reserve = reserve(&liquidity_pair[0]);
+    let mut quote_reserve = reserve(&liquidity_pair[1]);
+    if swapped_in_pair {
+        token_reserve += swap.amount;
+        quote_reserve = quote_reserve.saturating_sub(swap.expected);
+    }
+    if pool.total_share.is_zero() || token_reserve.is_zero() || quote_reserve.is_zero() {
+        return Ok(None);
+    }
+    Ok(Some(std::cmp::min(
+        token_amount.multiply_ratio(pool.total_share, token_reserve),
+        swap.expected
+            .multiply_ratio(pool.total_share, quote_reserve),
+    )))
+}
+
+/// Provides liquidity with the reflection token kept by liquify and the quote asset the
+/// liquidity swap actually returned, measured from the treasury's balance
+pub fn provide_swapped_liquidity(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let pending = PENDING_LIQUIDITY.load(deps.storage)?;
+    PENDING_LIQUIDITY.remove(deps.storage);
+
+    let quote_balance = query_asset_balance(
+        &deps.querier,
+        &pending.quote_asset,
+        env.contract.address.clone(),
+    )?;
+    let quote_amount = quote_balance.saturating_sub(pending.quote_balance);
+    if quote_amount.is_zero() {
+        set_leg_status(
+            deps.storage,
+            LIQUIDITY_SWAP_REPLY_ID,
+            LegStatus::Failed {
+                error: "Liquidity swap returned nothing".to_string(),
             },
-            ask_asset_info: reflection_pair[1].clone(),
-        };
+        )?;
+        return Ok(Response::new().add_attribute("quote_received", quote_amount));
+    }
 
-        // 2. Start with a mutable vector containing only the first operation.
-        let mut operations = vec![first_op];
+    let token = TOKEN.load(deps.storage)?;
+    let liquidity_pair = LIQUIDITY_PAIR.load(deps.storage)?;
+    let liquidity_pair_contract = LIQUIDITY_PAIR_CONTRACT.load(deps.storage)?;
+    let slippage = SLIPPAGE.may_load(deps.storage)?.unwrap_or_default();
+    let deadline = Some(env.block.time.seconds() + slippage.deadline_seconds);
 
-        // 3. Only add the second swap if the final target is a different token.
-        if reflection_pair[0] != self_token_info {
-            // 4. If they are different, add the second swap operation.
-            // This swaps the intermediate asset (e.g., INJ) into the final reward (e.g., DOJO).
-            let second_op = choice::router::SwapOperation::Choice {
-                offer_asset_info: reflection_pair[1].clone(),
-                ask_asset_info: reflection_pair[0].clone(),
+    let mut messages: Vec<SubMsg> = vec![];
+    let mut allowances = vec![Asset {
+        info: liquidity_pair[0].clone(),
+        amount: pending.token_amount,
+    }];
+
+    // Increases allowance of reflection token to liquidity pair contract (allows adding liquidity)
+    messages.push(SubMsg::new(WasmMsg::Execute {
+        contract_addr: token.to_string(),
+        msg: to_json_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+            spender: liquidity_pair_contract.clone(),
+            amount: pending.token_amount,
+            expires: None,
+        })?,
+        funds: vec![],
+    }));
+
+    let assets: [Asset; 2] = [
+        Asset {
+            amount: pending.token_amount,
+            info: liquidity_pair[0].clone(),
+        },
+        Asset {
+            amount: quote_amount,
+            info: pending.quote_asset.clone(),
+        },
+    ];
+    let provide_msg = LIQUIDITY_DEX
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .provide_liquidity_msg(assets, Some(slippage.slippage_tolerance), deadline)?;
+
+    // We formulate a ProvideLiquidity message to add reflection token liquidity to the pool
+    let funds = match pending.quote_asset.clone() {
+        // If the asset is a native token, we provide liquidity via a denom message
+        AssetInfo::NativeToken { denom } => vec![coin(quote_amount.u128(), denom)],
+        // If asset is a CW20, we provide liquidity via increase allowance message
+        AssetInfo::Token { contract_addr } => {
+            allowances.push(Asset {
+                info: pending.quote_asset.clone(),
+                amount: quote_amount,
+            });
+            messages.push(SubMsg::new(WasmMsg::Execute {
+                contract_addr,
+                msg: to_json_binary(&Cw20ExecuteMsg::IncreaseAllowance {
+                    spender: liquidity_pair_contract.clone(),
+                    amount: quote_amount,
+                    expires: None,
+                })?,
+                funds: vec![],
+            }));
+            vec![]
+        }
+    };
+    // The allowances are granted outside the provide, its failure reply revokes them
+    PENDING_PROVIDE.save(
+        deps.storage,
+        &PendingProvide {
+            spender: liquidity_pair_contract.clone(),
+            allowances,
+        },
+    )?;
+    messages.push(SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: liquidity_pair_contract,
+            msg: provide_msg,
+            funds,
+        },
+        PROVIDE_LIQUIDITY_REPLY_ID,
+    ));
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attribute("quote_received", quote_amount))
+}
+
+/// Records the LP tokens minted by the liquidity leg of a liquify, and burns them when the
+/// LP burn mode is set
+pub fn record_lp_minted(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    PENDING_PROVIDE.remove(deps.storage);
+    let mut snapshot = LIQUIFY_SNAPSHOT.load(deps.storage)?;
+    let lp_token = LIQUIDITY_TOKEN.load(deps.storage)?;
+    let lp_balance = query_balance(
+        &deps.querier,
+        Addr::unchecked(&lp_token),
+        env.contract.address.clone(),
+    )?;
+    let lp_minted = lp_balance.saturating_sub(snapshot.lp_balance);
+
+    let mut response = Response::new().add_attribute("lp_minted", lp_minted);
+    let mut stats = STATS.may_load(deps.storage)?.unwrap_or_default();
+    stats.total_lp_minted += lp_minted;
+    // Only the freshly minted LP is burned, what the treasury already held stays under the lock
+    if let Some(mode) = LP_BURN.may_load(deps.storage)? {
+        if !lp_minted.is_zero() {
+            let msg = match mode {
+                LpBurnMode::Burn {} => Cw20ExecuteMsg::Burn { amount: lp_minted },
+                LpBurnMode::Send { recipient } => Cw20ExecuteMsg::Transfer {
+                    recipient,
+                    amount: lp_minted,
+                },
             };
-            operations.push(second_op);
+            response = response
+                .add_message(WasmMsg::Execute {
+                    contract_addr: lp_token,
+                    msg: to_json_binary(&msg)?,
+                    funds: vec![],
+                })
+                .add_attribute("lp_burned", lp_minted);
+            stats.total_lp_burned += lp_minted;
         }
+    }
+    STATS.save(deps.storage, &stats)?;
+    set_leg_status(
+        deps.storage,
+        PROVIDE_LIQUIDITY_REPLY_ID,
+        LegStatus::Succeeded {},
+    )?;
+    let response = settle_leg_success(deps.storage, &env, response)?;
+
+    // The liquidity leg may have moved the reward asset (when it is also the quote asset),
+    // so the reflection leg is measured from the balance left after it
+    if let Some(reward_asset) = &snapshot.reward_asset {
+        snapshot.reward_balance =
+            query_asset_balance(&deps.querier, reward_asset, env.contract.address)?;
+    }
+    snapshot.lp_balance = lp_balance;
+    LIQUIFY_SNAPSHOT.save(deps.storage, &snapshot)?;
+
+    Ok(response)
+}
 
-        // 5. Execute the swap(s). The `operations` vector now contains either one or two steps.
-        messages.push(WasmMsg::Execute {
+/// Records the reward token acquired by the reflection leg of a liquify
+pub fn record_reward_acquired(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let mut snapshot = LIQUIFY_SNAPSHOT.load(deps.storage)?;
+    let reward_asset = match &snapshot.reward_asset {
+        Some(asset) => asset.clone(),
+        None => return Ok(Response::default()),
+    };
+    let reward_balance =
+        query_asset_balance(&deps.querier, &reward_asset, env.contract.address.clone())?;
+    let reward_acquired = reward_balance.saturating_sub(snapshot.reward_balance);
+
+    let mut stats = STATS.may_load(deps.storage)?.unwrap_or_default();
+    stats.total_reward_acquired += reward_acquired;
+    STATS.save(deps.storage, &stats)?;
+    set_leg_status(
+        deps.storage,
+        REFLECTION_SWAP_REPLY_ID,
+        LegStatus::Succeeded {},
+    )?;
+
+    snapshot.reward_balance = reward_balance;
+    LIQUIFY_SNAPSHOT.save(deps.storage, &snapshot)?;
+
+    let mut response = settle_leg_success(
+        deps.storage,
+        &env,
+        Response::new().add_attribute("reward_acquired", reward_acquired),
+    )?;
+
+    // Reserve the new rewards for the holders and let the token raise their dividend index
+    let dividends_enabled = DIVIDENDS_ENABLED.may_load(deps.storage)?.unwrap_or(false);
+    if dividends_enabled && !reward_acquired.is_zero() {
+        let unclaimed = UNCLAIMED_DIVIDENDS
+            .may_load(deps.storage)?
+            .unwrap_or_default();
+        UNCLAIMED_DIVIDENDS.save(deps.storage, &(unclaimed + reward_acquired))?;
+        DIVIDEND_ASSET.save(deps.storage, &reward_asset)?;
+
+        let token = TOKEN.load(deps.storage)?;
+        response = response.add_message(WasmMsg::Execute {
             contract_addr: token.to_string(),
-            msg: to_json_binary(&cw20::Cw20ExecuteMsg::Send {
-                contract: router.to_string(),
-                amount: reflect_amt,
-                msg: to_json_binary(&choice::router::ExecuteMsg::ExecuteSwapOperations {
-                    operations,
-                    minimum_receive: None,
-                    to: None, // target token is sent here into treasury
-                    deadline: None,
-                })?,
+            msg: to_json_binary(&TokenExecuteMsg::DistributeDividends {
+                amount: reward_acquired,
             })?,
             funds: vec![],
         });
     }
 
-    if burn_amt > Uint128::zero() {
-        // Burn
-        messages.push(WasmMsg::Execute {
-            contract_addr: token.to_string(),
-            msg: to_json_binary(&cw20::Cw20ExecuteMsg::Burn { amount: burn_amt })?,
-            funds: vec![],
-        });
+    Ok(response)
+}
+
+/// Enables forwarding the reward token acquired by liquify to the holders as claimable dividends
+pub fn set_dividends(
+    deps: DepsMut,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    DIVIDENDS_ENABLED.save(deps.storage, &enabled)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_dividends")
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+/// Pays a holder's claimed dividends, only callable by the token
+pub fn pay_dividend(
+    deps: DepsMut,
+    info: MessageInfo,
+    recipient: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let token = TOKEN.load(deps.storage)?;
+    if info.sender != token {
+        return Err(ContractError::Unauthorized {});
     }
 
-    let res = Response::new().add_messages(messages);
+    let unclaimed = UNCLAIMED_DIVIDENDS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    UNCLAIMED_DIVIDENDS.save(
+        deps.storage,
+        &unclaimed.checked_sub(amount).map_err(StdError::from)?,
+    )?;
+
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let asset = Asset {
+        info: DIVIDEND_ASSET.load(deps.storage)?,
+        amount,
+    };
+
+    Ok(Response::new()
+        .add_message(asset.clone().into_msg(recipient.clone())?)
+        .add_attribute("action", "pay_dividend")
+        .add_attribute("recipient", recipient)
+        .add_attribute("amount", asset.to_string()))
+}
 
-    Ok(res)
+/// Sets the max spread, slippage tolerance and deadline of the liquify legs
+pub fn set_slippage(
+    deps: DepsMut,
+    info: MessageInfo,
+    config: SlippageConfig,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    if config.max_spread > Decimal::one() || config.slippage_tolerance > Decimal::one() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "max_spread and slippage_tolerance must be <= 1",
+        )));
+    }
+    // A zero deadline would expire every leg in the block it is sent
+    if config.deadline_seconds == 0 {
+        return Err(ContractError::Std(StdError::generic_err(
+            "deadline_seconds must be greater than 0",
+        )));
+    }
+    SLIPPAGE.save(deps.storage, &config)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_slippage")
+        .add_attribute("max_spread", config.max_spread.to_string())
+        .add_attribute("slippage_tolerance", config.slippage_tolerance.to_string())
+        .add_attribute("deadline_seconds", config.deadline_seconds.to_string()))
+}
+
+pub fn query_dividends(storage: &dyn Storage) -> StdResult<DividendsResponse> {
+    Ok(DividendsResponse {
+        enabled: DIVIDENDS_ENABLED.may_load(storage)?.unwrap_or(false),
+        asset: DIVIDEND_ASSET.may_load(storage)?,
+        unclaimed: UNCLAIMED_DIVIDENDS.may_load(storage)?.unwrap_or_default(),
+    })
+}
+
+/// Amount of `asset` held on behalf of others, which the admin cannot withdraw
+pub fn reserved_amount(storage: &dyn Storage, asset: &AssetInfo) -> StdResult<Uint128> {
+    let mut reserved = match DIVIDEND_ASSET.may_load(storage)? {
+        Some(dividend_asset) if dividend_asset.equal(asset) => {
+            UNCLAIMED_DIVIDENDS.may_load(storage)?.unwrap_or_default()
+        }
+        _ => Uint128::zero(),
+    };
+    let token = AssetInfo::Token {
+        contract_addr: TOKEN.load(storage)?.to_string(),
+    };
+    if asset.equal(&token) {
+        reserved += pending_keeper_bounty(storage)?;
+    }
+    Ok(reserved)
+}
+
+/// Keeper bounty set aside by the last liquify while one of its legs has yet to reply. Once
+/// they all replied without success it is not owed anymore.
+fn pending_keeper_bounty(storage: &dyn Storage) -> StdResult<Uint128> {
+    let Some(bounty) = PENDING_KEEPER_BOUNTY.may_load(storage)? else {
+        return Ok(Uint128::zero());
+    };
+    let legs_pending = LAST_LIQUIFY.may_load(storage)?.is_some_and(|outcome| {
+        [outcome.liquidity, outcome.reflection, outcome.burn]
+            .iter()
+            .any(|leg| leg.status == LegStatus::Pending {})
+    });
+    Ok(if legs_pending {
+        bounty.amount
+    } else {
+        Uint128::zero()
+    })
 }
 
-/// Used to simulate swap operations against choice pair
-pub fn simulate(
+/// The asset the reflection leg ends up holding: the target token of the reflection pair,
+/// or the quote token when the target is the reflection token itself
+pub fn reward_asset_info(token: &Addr, reflection_pair: &[AssetInfo; 2]) -> AssetInfo {
+    let self_token_info = AssetInfo::Token {
+        contract_addr: token.to_string(),
+    };
+    if reflection_pair[0] == self_token_info {
+        reflection_pair[1].clone()
+    } else {
+        reflection_pair[0].clone()
+    }
+}
+
+/// The asset the reflection leg ends up holding: the last asset of the reflection route when
+/// one is set, otherwise derived from the reflection pair
+pub fn reward_asset(
+    storage: &dyn Storage,
+    token: &Addr,
+    reflection_pair: &[AssetInfo; 2],
+) -> StdResult<AssetInfo> {
+    Ok(match REFLECTION_ROUTE.may_load(storage)? {
+        Some(operations) => operations[operations.len() - 1].get_target_asset_info(),
+        None => reward_asset_info(token, reflection_pair),
+    })
+}
+
+/// Claims are paid in the asset they accrued in, so it cannot change while any are pending
+fn ensure_reward_unchanged(
+    storage: &dyn Storage,
+    reward_asset: &AssetInfo,
+) -> Result<(), ContractError> {
+    let unclaimed = UNCLAIMED_DIVIDENDS.may_load(storage)?.unwrap_or_default();
+    if let Some(dividend_asset) = DIVIDEND_ASSET.may_load(storage)? {
+        if !unclaimed.is_zero() && *reward_asset != dividend_asset {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Reward token cannot change while dividends are unclaimed",
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Default reflection route through the router, built from the reflection pair
+pub fn reflection_operations(token: &Addr, reflection_pair: &[AssetInfo; 2]) -> Vec<SwapOperation> {
+    let self_token_info = AssetInfo::Token {
+        contract_addr: token.to_string(),
+    };
+    // 1. Define the first swap operation, which is always required.
+    // This swaps your reflection token into the intermediate asset (e.g., INJ).
+    let first_op = SwapOperation::Choice {
+        offer_asset_info: self_token_info.clone(),
+        ask_asset_info: reflection_pair[1].clone(),
+    };
+
+    // 2. Start with a mutable vector containing only the first operation.
+    let mut operations = vec![first_op];
+
+    // 3. Only add the second swap if the final target is a different token.
+    if reflection_pair[0] != self_token_info {
+        // 4. If they are different, add the second swap operation.
+        // This swaps the intermediate asset (e.g., INJ) into the final reward (e.g., DOJO).
+        let second_op = SwapOperation::Choice {
+            offer_asset_info: reflection_pair[1].clone(),
+            ask_asset_info: reflection_pair[0].clone(),
+        };
+        operations.push(second_op);
+    }
+    operations
+}
+
+/// Swap hook for a pair, with the belief price of the pair's simulation, and the simulated
+/// return
+pub fn pair_swap_hook(
     querier: &QuerierWrapper,
-    pair_contract: String,
-    offer_asset: &Asset,
-) -> StdResult<SimulationResponse> {
-    querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: pair_contract,
-        msg: to_json_binary(&PairQueryMsg::Simulation {
-            offer_asset: offer_asset.clone(),
-        })?,
-    }))
+    dex: Dex,
+    pair_contract: &str,
+    offer_info: &AssetInfo,
+    amount: Uint128,
+    slippage: &SlippageConfig,
+    deadline: Option<u64>,
+) -> StdResult<(Binary, Uint128)> {
+    let simulation = dex.simulate(
+        querier,
+        pair_contract,
+        &Asset {
+            amount,
+            info: offer_info.clone(),
+        },
+    )?;
+    let belief_price = Decimal::checked_from_ratio(
+        amount,
+        simulation.return_amount + simulation.commission_amount,
+    )
+    .ok();
+    let msg = dex.swap_hook(belief_price, Some(slippage.max_spread), deadline)?;
+    Ok((msg, simulation.return_amount))
+}
+
+/// Swap hook for the router, and the router's simulated return. The simulated return, less the
+/// max spread, is the least the swap may return.
+pub fn router_swap_hook(
+    querier: &QuerierWrapper,
+    dex: Dex,
+    router: &str,
+    operations: Vec<SwapOperation>,
+    amount: Uint128,
+    slippage: &SlippageConfig,
+    deadline: Option<u64>,
+) -> StdResult<(Binary, Uint128)> {
+    let simulated = dex.simulate_route(querier, router, amount, &operations)?;
+    let minimum_receive = simulated.mul_floor(Decimal::one() - slippage.max_spread);
+    let msg = dex.route_hook(
+        operations,
+        Some(minimum_receive),
+        Some(slippage.max_spread),
+        deadline,
+    )?;
+    Ok((msg, simulated))
+}
+
+/// Checks a route starts from the reflection token and its hops connect, and that the router
+/// can simulate it. Returns the asset the route ends in.
+pub fn validate_route(
+    deps: Deps,
+    operations: &[SwapOperation],
+) -> Result<AssetInfo, ContractError> {
+    let token = AssetInfo::Token {
+        contract_addr: TOKEN.load(deps.storage)?.to_string(),
+    };
+    let (Some(first), Some(last)) = (operations.first(), operations.last()) else {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Route must have at least one hop",
+        )));
+    };
+    if !first.get_offer_asset_info().equal(&token) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Route must start from the reflection token",
+        )));
+    }
+    if operations.windows(2).any(|hops| {
+        !hops[0]
+            .get_target_asset_info()
+            .equal(&hops[1].get_offer_asset_info())
+    }) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Route hops do not connect",
+        )));
+    }
+    let ask = last.get_target_asset_info();
+    if ask.equal(&token) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Route cannot end in the reflection token",
+        )));
+    }
+
+    let router = ROUTER.load(deps.storage)?;
+    let simulated = ROUTER_DEX
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .simulate_route(
+            &deps.querier,
+            &router,
+            Uint128::new(ROUTE_PROBE_AMOUNT),
+            operations,
+        )?;
+    if simulated.is_zero() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Route returns nothing",
+        )));
+    }
+    Ok(ask)
+}
+
+/// Stores the route of the reflection swap, its last asset becomes the reward token.
+/// `None` restores the route derived from the reflection pair.
+pub fn set_reflection_route(
+    deps: DepsMut,
+    info: MessageInfo,
+    operations: Option<Vec<SwapOperation>>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    let token = TOKEN.load(deps.storage)?;
+    let reward_asset = match &operations {
+        Some(operations) => Some(validate_route(deps.as_ref(), operations)?),
+        None => REFLECTION_PAIR
+            .may_load(deps.storage)?
+            .map(|reflection_pair| reward_asset_info(&token, &reflection_pair)),
+    };
+    if let Some(reward_asset) = &reward_asset {
+        ensure_reward_unchanged(deps.storage, reward_asset)?;
+    }
+    match &operations {
+        Some(operations) => REFLECTION_ROUTE.save(deps.storage, operations)?,
+        None => REFLECTION_ROUTE.remove(deps.storage),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_reflection_route")
+        .add_attribute("hops", operations.unwrap_or_default().len().to_string())
+        .add_attribute(
+            "reward_asset",
+            reward_asset
+                .map(|asset| asset.to_string())
+                .unwrap_or_default(),
+        ))
+}
+
+/// Stores the route of the liquidity swap, it must end in the quote asset of the liquidity
+/// pair. `None` swaps directly in the liquidity pair.
+pub fn set_liquidity_route(
+    deps: DepsMut,
+    info: MessageInfo,
+    operations: Option<Vec<SwapOperation>>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    match &operations {
+        Some(operations) => {
+            let ask = validate_route(deps.as_ref(), operations)?;
+            let liquidity_pair = LIQUIDITY_PAIR.load(deps.storage)?;
+            if !ask.equal(&liquidity_pair[1]) {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Route must end in the quote asset of the liquidity pair",
+                )));
+            }
+            LIQUIDITY_ROUTE.save(deps.storage, operations)?;
+        }
+        None => LIQUIDITY_ROUTE.remove(deps.storage),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_liquidity_route")
+        .add_attribute("hops", operations.unwrap_or_default().len().to_string()))
+}
+
+/// Replaces the router, the stored routes are checked against the new one
+pub fn set_router(
+    deps: DepsMut,
+    info: MessageInfo,
+    router: String,
+    dex: Option<Dex>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    deps.api.addr_validate(&router)?;
+    let dex = dex.unwrap_or_default();
+    ROUTER.save(deps.storage, &router)?;
+    ROUTER_DEX.save(deps.storage, &dex)?;
+    for route in [REFLECTION_ROUTE, LIQUIDITY_ROUTE] {
+        if let Some(operations) = route.may_load(deps.storage)? {
+            validate_route(deps.as_ref(), &operations)?;
+        }
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_router")
+        .add_attribute("router", router))
 }
 
 pub fn query_balance(querier: &QuerierWrapper, token: Addr, address: Addr) -> StdResult<Uint128> {
@@ -372,20 +1612,47 @@ pub fn query_balance(querier: &QuerierWrapper, token: Addr, address: Addr) -> St
     Ok(response.balance)
 }
 
+/// Queries the balance of a CW20 or native asset held by `address`
+pub fn query_asset_balance(
+    querier: &QuerierWrapper,
+    asset: &AssetInfo,
+    address: Addr,
+) -> StdResult<Uint128> {
+    match asset {
+        AssetInfo::Token { contract_addr } => {
+            query_balance(querier, Addr::unchecked(contract_addr), address)
+        }
+        AssetInfo::NativeToken { denom } => Ok(querier.query_balance(address, denom)?.amount),
+    }
+}
+
 // Check below for pair ordering
 // 1. This contract address (reflection token)
 // 2. The quote token (inj)
 pub fn set_liquidity_pair(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     asset_infos: [AssetInfo; 2],
     pair_contract: String,
+    dex: Option<Dex>,
 ) -> Result<Response, ContractError> {
     ensure_admin(&deps, &info)?;
+    let dex = dex.unwrap_or_default();
+    if let Some(operations) = LIQUIDITY_ROUTE.may_load(deps.storage)? {
+        if !operations[operations.len() - 1]
+            .get_target_asset_info()
+            .equal(&asset_infos[1])
+        {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Liquidity route does not end in asset_infos[1]",
+            )));
+        }
+    }
     let reflection_pair = REFLECTION_PAIR.load(deps.storage);
     LIQUIDITY_PAIR.save(deps.storage, &asset_infos)?;
     LIQUIDITY_PAIR_CONTRACT.save(deps.storage, &pair_contract)?;
+    LIQUIDITY_DEX.save(deps.storage, &dex)?;
 
     match reflection_pair {
         Err(_) => {}
@@ -400,22 +1667,39 @@ pub fn set_liquidity_pair(
         }
     };
 
-    let response: PairInfo = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: pair_contract,
-        msg: to_json_binary(&PairQueryMsg::Pair {})?,
-    }))?;
+    let response = dex.query_pair(&deps.querier, &pair_contract)?;
 
-    match response.asset_infos[0].clone() {
-        AssetInfo::Token { contract_addr } => {
+    match response.asset_infos.first().cloned() {
+        Some(AssetInfo::Token { contract_addr }) => {
             deps.api.addr_validate(&contract_addr.to_string())?;
         }
-        AssetInfo::NativeToken { denom: _ } => {
+        _ => {
             return Err(ContractError::Std(StdError::generic_err(
                 "token should be cw20",
             )));
         }
     };
 
+    // Moving to another pair would free the locked LP through `withdraw_token`
+    if let Some(lp_token) = LIQUIDITY_TOKEN.may_load(deps.storage)? {
+        if LP_LOCK.exists(deps.storage)
+            && lp_token != response.liquidity_token
+            && !query_balance(
+                &deps.querier,
+                Addr::unchecked(&lp_token),
+                env.contract.address.clone(),
+            )?
+            .is_zero()
+        {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Cannot change the liquidity pair while holding locked LP",
+            )));
+        }
+        // Withdrawals of the old LP token do not count toward the release of the new one
+        if lp_token != response.liquidity_token {
+            LP_WITHDRAWN.remove(deps.storage);
+        }
+    }
     LIQUIDITY_TOKEN.save(deps.storage, &response.liquidity_token.to_string())?;
 
     response
@@ -442,11 +1726,21 @@ pub fn set_reflection_pair(
     info: MessageInfo,
     asset_infos: [AssetInfo; 2],
     pair_contract: String,
+    dex: Option<Dex>,
 ) -> Result<Response, ContractError> {
     ensure_admin(&deps, &info)?;
+    let dex = dex.unwrap_or_default();
+
+    let token = TOKEN.load(deps.storage)?;
+    ensure_reward_unchanged(
+        deps.storage,
+        &reward_asset(deps.storage, &token, &asset_infos)?,
+    )?;
+
     let liquidity_pair = LIQUIDITY_PAIR.load(deps.storage);
     REFLECTION_PAIR.save(deps.storage, &asset_infos)?;
     REFLECTION_PAIR_CONTRACT.save(deps.storage, &pair_contract)?;
+    REFLECTION_DEX.save(deps.storage, &dex)?;
 
     match liquidity_pair {
         Err(_) => {}
@@ -461,10 +1755,7 @@ pub fn set_reflection_pair(
         }
     };
 
-    let response: PairInfo = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: pair_contract,
-        msg: to_json_binary(&PairQueryMsg::Pair {})?,
-    }))?;
+    let response = dex.query_pair(&deps.querier, &pair_contract)?;
 
     response
         .asset_infos
@@ -481,6 +1772,357 @@ pub fn set_reflection_pair(
     Ok(Response::default())
 }
 
+/// Sets the LP time lock. The first lock must run for at least `MIN_LP_LOCK_SECONDS`, then
+/// the unlock time and release duration can only be extended.
+pub fn set_lp_lock(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    unlock_time: u64,
+    release_duration: u64,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    match LP_LOCK.may_load(deps.storage)? {
+        Some(lock)
+            if unlock_time < lock.unlock_time || release_duration < lock.release_duration =>
+        {
+            return Err(ContractError::Std(StdError::generic_err(
+                "The LP lock can only be extended",
+            )));
+        }
+        None if unlock_time < env.block.time.seconds().saturating_add(MIN_LP_LOCK_SECONDS) => {
+            return Err(ContractError::Std(StdError::generic_err(format!(
+                "Unlock time must be at least {} seconds away",
+                MIN_LP_LOCK_SECONDS
+            ))));
+        }
+        _ => {}
+    }
+    LP_LOCK.save(
+        deps.storage,
+        &LpLock {
+            unlock_time,
+            release_duration,
+        },
+    )?;
+    Ok(Response::new()
+        .add_attribute("action", "set_lp_lock")
+        .add_attribute("unlock_time", unlock_time.to_string())
+        .add_attribute("release_duration", release_duration.to_string()))
+}
+
+/// Sets the buyback and burn mode
+pub fn set_buyback(
+    deps: DepsMut,
+    info: MessageInfo,
+    config: Option<BuybackConfig>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    let mut response = Response::new().add_attribute("action", "set_buyback");
+    match config {
+        Some(config) => {
+            if config.max_spread > Decimal::one() {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "max_spread must be <= 1",
+                )));
+            }
+            BUYBACK.save(deps.storage, &config)?;
+            response = response
+                .add_attribute("max_per_run", config.max_per_run)
+                .add_attribute("max_spread", config.max_spread.to_string())
+                .add_attribute("interval_seconds", config.interval_seconds.to_string())
+                .add_attribute("on_liquify", config.on_liquify.to_string());
+        }
+        None => BUYBACK.remove(deps.storage),
+    }
+    Ok(response)
+}
+
+/// Time the next buyback may run, `None` when buybacks are disabled
+fn next_buyback(storage: &dyn Storage) -> StdResult<Option<u64>> {
+    let Some(config) = BUYBACK.may_load(storage)? else {
+        return Ok(None);
+    };
+    Ok(Some(match LAST_BUYBACK.may_load(storage)? {
+        Some(last) => last.saturating_add(config.interval_seconds),
+        None => 0,
+    }))
+}
+
+/// Buyback run at the end of a liquify. It replies on error, so a failing buyback does not
+/// revert the liquify and waits for the interval like a successful one.
+fn buyback_trigger(storage: &dyn Storage, env: &Env) -> StdResult<Option<SubMsg>> {
+    let on_liquify = BUYBACK
+        .may_load(storage)?
+        .is_some_and(|config| config.on_liquify);
+    match next_buyback(storage)? {
+        Some(next) if on_liquify && next <= env.block.time.seconds() => {
+            Ok(Some(SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    msg: to_json_binary(&ExecuteMsg::Buyback {})?,
+                    funds: vec![],
+                },
+                BUYBACK_TRIGGER_REPLY_ID,
+            )))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Quote asset the buyback may spend: the treasury's balance less unclaimed dividends
+fn buyback_available(deps: Deps, env: &Env, quote: &AssetInfo) -> StdResult<Uint128> {
+    let balance = query_asset_balance(&deps.querier, quote, env.contract.address.clone())?;
+    Ok(balance.saturating_sub(reserved_amount(deps.storage, quote)?))
+}
+
+/// Swaps up to `max_per_run` of the quote asset into the reflection token in the liquidity
+/// pair, the reply burns what the swap returned
+pub fn buyback(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let config = BUYBACK
+        .may_load(deps.storage)?
+        .ok_or_else(|| StdError::generic_err("Buyback is disabled"))?;
+    let next = next_buyback(deps.storage)?.unwrap_or_default();
+    if next > env.block.time.seconds() {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Next buyback at {}",
+            next
+        ))));
+    }
+
+    let token = TOKEN.load(deps.storage)?;
+    let liquidity_pair = LIQUIDITY_PAIR.load(deps.storage)?;
+    let liquidity_pair_contract = LIQUIDITY_PAIR_CONTRACT.load(deps.storage)?;
+    let dex = LIQUIDITY_DEX.may_load(deps.storage)?.unwrap_or_default();
+    let quote = liquidity_pair[1].clone();
+    let amount = buyback_available(deps.as_ref(), &env, &quote)?.min(config.max_per_run);
+    if amount.is_zero() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "No quote asset to buy back with",
+        )));
+    }
+
+    let slippage = SLIPPAGE.may_load(deps.storage)?.unwrap_or_default();
+    let deadline = Some(env.block.time.seconds() + slippage.deadline_seconds);
+    let buyback_slippage = SlippageConfig {
+        max_spread: config.max_spread,
+        ..slippage
+    };
+    let msg = match &quote {
+        AssetInfo::NativeToken { denom } => {
+            let offer_asset = Asset {
+                info: quote.clone(),
+                amount,
+            };
+            let simulation = dex.simulate(&deps.querier, &liquidity_pair_contract, &offer_asset)?;
+            let belief_price = Decimal::checked_from_ratio(
+                amount,
+                simulation.return_amount + simulation.commission_amount,
+            )
+            .ok();
+            WasmMsg::Execute {
+                contract_addr: liquidity_pair_contract,
+                msg: dex.swap_msg(offer_asset, belief_price, Some(config.max_spread), deadline)?,
+                funds: vec![coin(amount.u128(), denom)],
+            }
+        }
+        AssetInfo::Token { contract_addr } => WasmMsg::Execute {
+            contract_addr: contract_addr.clone(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Send {
+                contract: liquidity_pair_contract.clone(),
+                amount,
+                msg: pair_swap_hook(
+                    &deps.querier,
+                    dex,
+                    &liquidity_pair_contract,
+                    &quote,
+                    amount,
+                    &buyback_slippage,
+                    deadline,
+                )?
+                .0,
+            })?,
+            funds: vec![],
+        },
+    };
+
+    PENDING_BUYBACK.save(
+        deps.storage,
+        &PendingBuyback {
+            offer_amount: amount,
+            token_balance: query_balance(&deps.querier, token, env.contract.address.clone())?,
+        },
+    )?;
+    LAST_BUYBACK.save(deps.storage, &env.block.time.seconds())?;
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_always(msg, BUYBACK_SWAP_REPLY_ID))
+        .add_attribute("action", "buyback")
+        .add_attribute("offer_amount", amount))
+}
+
+/// Records the time of a buyback triggered by liquify that failed. The failed buyback was
+/// reverted with its own record of the run, so without this it would be retried on every liquify.
+pub fn record_buyback_failure(
+    deps: DepsMut,
+    env: Env,
+    result: SubMsgResult,
+) -> Result<Response, ContractError> {
+    let SubMsgResult::Err(error) = result else {
+        return Ok(Response::default());
+    };
+    LAST_BUYBACK.save(deps.storage, &env.block.time.seconds())?;
+    Ok(Response::new()
+        .add_attribute("buyback_failed", "true")
+        .add_attribute("error", error))
+}
+
+/// Burns the reflection token returned by a buyback swap, measured from the treasury's
+/// balance. A failed buyback keeps its quote asset in the treasury.
+pub fn burn_bought_back(
+    deps: DepsMut,
+    env: Env,
+    result: SubMsgResult,
+) -> Result<Response, ContractError> {
+    let pending = PENDING_BUYBACK.may_load(deps.storage)?;
+    PENDING_BUYBACK.remove(deps.storage);
+    let pending = match (result, pending) {
+        (SubMsgResult::Err(error), _) => {
+            return Ok(Response::new()
+                .add_attribute("buyback_failed", "true")
+                .add_attribute("error", error))
+        }
+        (_, Some(pending)) => pending,
+        (_, None) => return Ok(Response::default()),
+    };
+
+    let token = TOKEN.load(deps.storage)?;
+    let token_balance = query_balance(&deps.querier, token.clone(), env.contract.address)?;
+    let bought = token_balance.saturating_sub(pending.token_balance);
+
+    let mut stats = STATS.may_load(deps.storage)?.unwrap_or_default();
+    stats.total_buyback_spent += pending.offer_amount;
+    stats.total_buyback_burned += bought;
+    STATS.save(deps.storage, &stats)?;
+
+    let mut response = Response::new()
+        .add_attribute("buyback_spent", pending.offer_amount)
+        .add_attribute("buyback_burned", bought);
+    if !bought.is_zero() {
+        response = response.add_message(WasmMsg::Execute {
+            contract_addr: token.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Burn { amount: bought })?,
+            funds: vec![],
+        });
+    }
+    Ok(response)
+}
+
+pub fn query_buyback(deps: Deps, env: &Env) -> StdResult<BuybackResponse> {
+    let asset = LIQUIDITY_PAIR
+        .may_load(deps.storage)?
+        .map(|liquidity_pair| liquidity_pair[1].clone());
+    let available = match &asset {
+        Some(asset) => buyback_available(deps, env, asset)?,
+        None => Uint128::zero(),
+    };
+    Ok(BuybackResponse {
+        config: BUYBACK.may_load(deps.storage)?,
+        asset,
+        available,
+        last_run: LAST_BUYBACK.may_load(deps.storage)?,
+        next_run: next_buyback(deps.storage)?,
+    })
+}
+
+/// Sets what happens to the LP tokens minted by liquify from now on
+pub fn set_lp_burn(
+    deps: DepsMut,
+    info: MessageInfo,
+    mode: Option<LpBurnMode>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    let mode_name = match &mode {
+        Some(LpBurnMode::Burn {}) => "burn".to_string(),
+        Some(LpBurnMode::Send { recipient }) => {
+            deps.api.addr_validate(recipient)?;
+            format!("send:{}", recipient)
+        }
+        None => "keep".to_string(),
+    };
+    match &mode {
+        Some(mode) => LP_BURN.save(deps.storage, mode)?,
+        None => LP_BURN.remove(deps.storage),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_lp_burn")
+        .add_attribute("mode", mode_name))
+}
+
+/// Withdraws released LP tokens to the admin
+pub fn withdraw_lp(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    ensure_admin(&deps, &info)?;
+    let vault = query_lp_lock(deps.as_ref(), &env)?;
+    let amount = amount.unwrap_or(vault.withdrawable);
+    if amount.is_zero() || amount > vault.withdrawable {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Amount exceeds the released LP",
+        )));
+    }
+    LP_WITHDRAWN.save(deps.storage, &(vault.withdrawn + amount))?;
+
+    let lp_token = vault.lp_token.unwrap_or_default();
+    Ok(Response::new()
+        .add_message(WasmMsg::Execute {
+            contract_addr: lp_token.clone(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: info.sender.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        })
+        .add_attribute("action", "withdraw_lp")
+        .add_attribute("lp_token", lp_token)
+        .add_attribute("amount", amount))
+}
+
+/// LP tokens held by the treasury and the part of them released by the lock. Releases count
+/// what was already withdrawn, so LP minted after the unlock is released at the same pace.
+pub fn query_lp_lock(deps: Deps, env: &Env) -> StdResult<LpLockResponse> {
+    let lp_token = LIQUIDITY_TOKEN.may_load(deps.storage)?;
+    let balance = match &lp_token {
+        Some(lp_token) => query_balance(
+            &deps.querier,
+            Addr::unchecked(lp_token),
+            env.contract.address.clone(),
+        )?,
+        None => Uint128::zero(),
+    };
+    let withdrawn = LP_WITHDRAWN.may_load(deps.storage)?.unwrap_or_default();
+    let lock = LP_LOCK.may_load(deps.storage)?;
+    let withdrawable = match &lock {
+        Some(lock) => (balance + withdrawn)
+            .mul_floor(lock.released(env.block.time.seconds()))
+            .saturating_sub(withdrawn)
+            .min(balance),
+        None => Uint128::zero(),
+    };
+    Ok(LpLockResponse {
+        lp_token,
+        balance,
+        locked: balance - withdrawable,
+        withdrawable,
+        withdrawn,
+        unlock_time: lock.as_ref().map(|lock| lock.unlock_time),
+        release_end: lock.map(|lock| lock.unlock_time.saturating_add(lock.release_duration)),
+    })
+}
+
 /// Sets minimum reflection token required to liquify
 pub fn set_min_liquify_amt(
     deps: DepsMut,
@@ -495,7 +2137,7 @@ pub fn set_min_liquify_amt(
 }
 
 /// Withdraws a CW20 or Native token of your choice from the contract.
-/// It is not allowed to withdraw the LP token itself.
+/// It is not allowed to withdraw the LP token itself, see `withdraw_lp`.
 pub fn withdraw_token(
     deps: DepsMut,
     env: Env,
@@ -528,7 +2170,16 @@ pub fn withdraw_token(
                 },
             )?;
 
-            if balance.balance.is_zero() {
+            // What is held for others, such as unclaimed dividends, stays with the treasury
+            let reserved = reserved_amount(
+                deps.storage,
+                &AssetInfo::Token {
+                    contract_addr: contract_addr.clone(),
+                },
+            )?;
+            let amount = balance.balance.saturating_sub(reserved);
+
+            if amount.is_zero() {
                 return Err(ContractError::Std(StdError::generic_err(
                     "No CW20 balance to withdraw",
                 )));
@@ -539,22 +2190,31 @@ pub fn withdraw_token(
                 contract_addr: contract_addr.to_string(),
                 msg: to_json_binary(&cw20::Cw20ExecuteMsg::Transfer {
                     recipient: info.sender.to_string(),
-                    amount: balance.balance,
+                    amount,
                 })?,
                 funds: vec![],
             }));
 
             response = response.add_attribute("withdraw_cw20_token", contract_addr.to_string());
-            response = response.add_attribute("withdraw_amount", balance.balance);
+            response = response.add_attribute("withdraw_amount", amount);
         }
         AssetInfo::NativeToken { denom } => {
             // --- NATIVE TOKEN LOGIC ---
 
             // Query the contract's native balance for the specified denomination
-            let balance = deps
+            let mut balance = deps
                 .querier
                 .query_balance(env.contract.address, denom.clone())?;
 
+            // What is held for others, such as unclaimed dividends, stays with the treasury
+            let reserved = reserved_amount(
+                deps.storage,
+                &AssetInfo::NativeToken {
+                    denom: denom.clone(),
+                },
+            )?;
+            balance.amount = balance.amount.saturating_sub(reserved);
+
             if balance.amount.is_zero() {
                 return Err(ContractError::Std(StdError::generic_err(
                     "No native balance to withdraw",
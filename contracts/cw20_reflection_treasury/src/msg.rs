@@ -1,7 +1,9 @@
+use choice::adapter::Dex;
 use choice::asset::AssetInfo;
+use choice::router::SwapOperation;
 use cosmwasm_std::Addr;
 use cosmwasm_std::Binary;
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Decimal, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -19,10 +21,43 @@ pub enum ExecuteMsg {
     SetLiquidityPair {
         asset_infos: [AssetInfo; 2],
         pair_contract: String,
+        /// DEX the pair belongs to, Choice when not given
+        dex: Option<Dex>,
     },
     SetReflectionPair {
         asset_infos: [AssetInfo; 2],
         pair_contract: String,
+        /// DEX the pair belongs to, Choice when not given
+        dex: Option<Dex>,
+    },
+    /// Locks the LP tokens until `unlock_time` (seconds), then releases them linearly over
+    /// `release_duration` seconds. The first lock must unlock at least 30 days ahead, then both
+    /// can only be extended. The token refuses to migrate the treasury until the release ends.
+    SetLpLock {
+        unlock_time: u64,
+        release_duration: u64,
+    },
+    /// Withdraws released LP tokens to the admin, all of them when `amount` is not given
+    WithdrawLp {
+        amount: Option<Uint128>,
+    },
+    /// Burns or sends away the LP tokens minted by every liquify, `None` keeps them in the
+    /// treasury
+    SetLpBurn {
+        mode: Option<LpBurnMode>,
+    },
+    /// Sets the buyback and burn mode, `None` disables it
+    SetBuyback {
+        config: Option<BuybackConfig>,
+    },
+    /// Buys the reflection token through the liquidity pair with the quote asset held by the
+    /// treasury and burns it. Anyone can call it once the buyback interval has passed.
+    Buyback {},
+    /// Replaces the router used by the reflection leg and the routes, Choice when `dex` is not
+    /// given. The stored routes must still simulate through the new router.
+    SetRouter {
+        router: String,
+        dex: Option<Dex>,
     },
     SetMinLiquify {
         min_liquify_amt: Uint128,
@@ -30,10 +65,105 @@ pub enum ExecuteMsg {
     WithdrawToken {
         asset: AssetInfo,
     },
+    /// Liquifies the treasury's balance. When a keeper bounty is set, the caller's bounty is set
+    /// aside before the split and paid once a leg of the run succeeds.
     Liquify {},
+    /// Sets the bounty paid to callers of `Liquify`, `None` disables it
+    SetKeeperBounty {
+        bounty: Option<KeeperBounty>,
+    },
     TransferAdmin {
         new_admin: String,
     },
+    /// Forwards the reward token acquired by liquify to the holders as claimable dividends
+    SetDividends {
+        enabled: bool,
+    },
+    /// Only callable by the token. Pays out dividends claimed by a holder.
+    PayDividend {
+        recipient: String,
+        amount: Uint128,
+    },
+    /// Sets the spread, slippage and deadline bounds passed on every liquify leg
+    SetSlippage {
+        config: SlippageConfig,
+    },
+    /// Sets the router route of the reflection swap, starting from the reflection token and
+    /// ending in the reward token. `None` derives it from the reflection pair.
+    SetReflectionRoute {
+        operations: Option<Vec<SwapOperation>>,
+    },
+    /// Sets the router route of the liquidity swap, starting from the reflection token and
+    /// ending in the quote asset of the liquidity pair. `None` swaps in the liquidity pair.
+    SetLiquidityRoute {
+        operations: Option<Vec<SwapOperation>>,
+    },
+}
+
+/// What happens to the LP tokens minted by liquify
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LpBurnMode {
+    /// Burns them, the LP token must support the CW20 `Burn` message
+    Burn {},
+    /// Sends them to a dead address
+    Send { recipient: String },
+}
+
+/// Reward in the reflection token for calling `Liquify`
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct KeeperBounty {
+    pub reward: KeeperReward,
+    /// Seconds after a paid bounty during which liquify pays none
+    pub min_interval_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum KeeperReward {
+    /// Share of the balance being liquified
+    Share { rate: Decimal },
+    /// Fixed amount, capped at the balance being liquified
+    Fixed { amount: Uint128 },
+}
+
+/// Buyback and burn of the reflection token with the quote asset of the liquidity pair sent
+/// to the treasury
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct BuybackConfig {
+    /// Most quote asset spent by one buyback
+    pub max_per_run: Uint128,
+    /// Max spread of the buyback swap
+    pub max_spread: Decimal,
+    /// Seconds between two buybacks
+    pub interval_seconds: u64,
+    /// Runs a buyback after each liquify when the interval has passed
+    pub on_liquify: bool,
+}
+
+/// Spread, slippage and deadline bounds of the liquify legs. The expected returns come from
+/// simulations in the same transaction, so the bounds only limit the price impact of the legs
+/// themselves; they do not protect against trades placed before the liquify, such as a
+/// sandwich.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SlippageConfig {
+    /// Max spread of the liquidity swap, and the shortfall allowed from the router's simulated
+    /// return of the reflection swap
+    pub max_spread: Decimal,
+    /// Slippage tolerance when providing liquidity
+    pub slippage_tolerance: Decimal,
+    /// Seconds after the liquify block within which each leg must execute, at least 1
+    pub deadline_seconds: u64,
+}
+
+impl Default for SlippageConfig {
+    fn default() -> Self {
+        SlippageConfig {
+            max_spread: Decimal::percent(5),
+            slippage_tolerance: Decimal::percent(5),
+            deadline_seconds: 300,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -41,6 +171,33 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
     Balance {},
     GetToken {},
+    /// Returns the admin, token, router, pairs, routes, slippage bounds and liquify threshold
+    Config {},
+    /// Returns the balance of every asset the treasury deals in
+    Holdings {},
+    /// Returns the lifetime liquify counters of the treasury
+    Stats {},
+    /// Returns the dividend settings and the reward token reserved for claims
+    Dividends {},
+    /// Returns the spread, slippage and deadline bounds of the liquify legs
+    Slippage {},
+    /// Returns the configured swap routes
+    Routes {},
+    /// Returns the LP tokens held by the treasury and their time lock
+    LpLock {},
+    /// Returns the LP burn mode. Return type: Option<LpBurnMode>.
+    LpBurn {},
+    /// Returns the buyback settings and the quote asset available to it
+    Buyback {},
+    /// Returns the keeper bounty and when it is next paid
+    KeeperBounty {},
+    /// Returns what each leg of the last liquify did. Return type: Option<LiquifyOutcome>.
+    LastLiquify {},
+    /// Returns what a liquify called by `keeper` would do now, without executing it. Not set
+    /// until both pairs are. Return type: Option<LiquifyPreviewResponse>.
+    LiquifyPreview {
+        keeper: Option<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
@@ -51,6 +208,203 @@ pub struct QueryTaxResponse {
     pub liquidity_amount: Uint128,
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct StatsResponse {
+    /// Total amount of reflection token processed by the liquify legs that succeeded
+    pub total_liquified: Uint128,
+    /// Total LP tokens received from providing liquidity
+    pub total_lp_minted: Uint128,
+    /// Part of the minted LP tokens burned or sent to the dead address
+    pub total_lp_burned: Uint128,
+    /// Total reward token received from the reflection swaps
+    pub total_reward_acquired: Uint128,
+    /// Number of liquify runs that dispatched at least one leg
+    pub liquify_runs: u64,
+    /// Total quote asset spent by buybacks
+    pub total_buyback_spent: Uint128,
+    /// Total reflection token bought back and burned
+    pub total_buyback_burned: Uint128,
+    /// Total reflection token paid to liquify callers
+    pub total_keeper_bounties: Uint128,
+}
+
+/// Legs of a liquify succeed or fail on their own, a failed leg leaves its funds in the
+/// treasury for the next run
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LiquifyOutcome {
+    pub height: u64,
+    pub liquidity: LegOutcome,
+    pub reflection: LegOutcome,
+    pub burn: LegOutcome,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LegOutcome {
+    /// Reflection token assigned to the leg
+    pub amount: Uint128,
+    pub status: LegStatus,
+}
+
+impl LegOutcome {
+    pub fn new(amount: Uint128) -> Self {
+        LegOutcome {
+            amount,
+            status: if amount.is_zero() {
+                LegStatus::Skipped {}
+            } else {
+                LegStatus::Pending {}
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LegStatus {
+    Skipped {},
+    Pending {},
+    Succeeded {},
+    Failed { error: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LiquifyPreviewResponse {
+    /// Reflection token held by the treasury
+    pub balance: Uint128,
+    pub min_liquify_amt: Uint128,
+    /// The balance is below `min_liquify_amt`, a liquify would do nothing
+    pub blocked: bool,
+    /// Liquidity leg, expecting the quote asset returned by swapping half of it
+    pub liquidity: LegPreview,
+    /// LP tokens expected from the liquidity leg, not set when the pool is empty
+    pub expected_lp: Option<Uint128>,
+    /// Reflection leg, expecting the reward asset returned by the swap
+    pub reflection: LegPreview,
+    pub burn_amount: Uint128,
+    /// Bounty set aside for the keeper before the split, zero without a keeper. It is only paid
+    /// if a leg succeeds, otherwise it stays in the treasury.
+    pub keeper_bounty: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LegPreview {
+    /// Reflection token assigned to the leg
+    pub amount: Uint128,
+    /// Simulated return of the leg's swap
+    pub expected: Option<Uint128>,
+    /// Why the leg's swap cannot be simulated, the leg would fail
+    pub error: Option<String>,
+}
+
+impl LegPreview {
+    pub fn new(amount: Uint128) -> Self {
+        LegPreview {
+            amount,
+            expected: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LpLockResponse {
+    pub lp_token: Option<String>,
+    /// LP tokens held by the treasury
+    pub balance: Uint128,
+    /// Part of the balance the admin cannot withdraw yet
+    pub locked: Uint128,
+    pub withdrawable: Uint128,
+    /// LP tokens withdrawn since the lock was set
+    pub withdrawn: Uint128,
+    /// Not set while the LP cannot be withdrawn at all
+    pub unlock_time: Option<u64>,
+    /// Time the whole balance is released
+    pub release_end: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigResponse {
+    pub admin: String,
+    pub token: String,
+    pub router: String,
+    pub router_dex: Dex,
+    pub liquidity_pair: Option<PairConfig>,
+    pub reflection_pair: Option<PairConfig>,
+    /// LP token of the liquidity pair
+    pub liquidity_token: Option<String>,
+    /// Asset the reflection leg buys
+    pub reward_asset: Option<AssetInfo>,
+    pub min_liquify_amt: Uint128,
+    pub slippage: SlippageConfig,
+    /// Router route of the reflection swap, derived from the reflection pair when not set
+    pub reflection_route: Option<Vec<SwapOperation>>,
+    /// Router route of the liquidity swap, which is made in the pair when not set
+    pub liquidity_route: Option<Vec<SwapOperation>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PairConfig {
+    pub asset_infos: [AssetInfo; 2],
+    pub contract: String,
+    pub dex: Dex,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct HoldingsResponse {
+    /// The reflection token
+    pub token: AssetHolding,
+    pub lp: Option<AssetHolding>,
+    pub reward: Option<AssetHolding>,
+    /// Quote asset of the liquidity pair
+    pub quote: Option<AssetHolding>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AssetHolding {
+    pub asset: AssetInfo,
+    pub balance: Uint128,
+    /// Part of the balance the admin cannot withdraw: unclaimed dividends, locked LP or the
+    /// keeper bounty of a liquify whose legs are running
+    pub reserved: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct KeeperBountyResponse {
+    pub bounty: Option<KeeperBounty>,
+    /// Block time of the last paid bounty
+    pub last_paid: Option<u64>,
+    /// Earliest block time a bounty is paid again
+    pub next_paid: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct BuybackResponse {
+    pub config: Option<BuybackConfig>,
+    /// Quote asset of the liquidity pair
+    pub asset: Option<AssetInfo>,
+    /// Quote asset the next buyback can spend, before the per run cap
+    pub available: Uint128,
+    /// Block time of the last buyback
+    pub last_run: Option<u64>,
+    /// Earliest block time of the next buyback
+    pub next_run: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RoutesResponse {
+    pub reflection: Option<Vec<SwapOperation>>,
+    pub liquidity: Option<Vec<SwapOperation>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DividendsResponse {
+    pub enabled: bool,
+    /// Asset the dividends are paid in
+    pub asset: Option<AssetInfo>,
+    /// Reward token distributed to holders and not yet claimed
+    pub unclaimed: Uint128,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct GetTokenResponse {
     pub address: String,
@@ -65,6 +419,20 @@ pub struct MigrateMsg {
 #[serde(rename_all = "snake_case")]
 pub enum TokenQueryMsg {
     QueryRates {},
+    TreasuryRates {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TreasuryRatesResponse {
+    pub reflection_rate: Decimal,
+    pub burn_rate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenExecuteMsg {
+    DistributeDividends { amount: Uint128 },
+    TreasuryLiquified {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
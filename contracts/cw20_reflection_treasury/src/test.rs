@@ -0,0 +1,1892 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::contract::{
+        execute, instantiate, reply, PendingProvide, BURN_REPLY_ID, BUYBACK_SWAP_REPLY_ID,
+        BUYBACK_TRIGGER_REPLY_ID, DIVIDEND_ASSET, LIQUIDITY_SWAP_REPLY_ID, MIN_LP_LOCK_SECONDS,
+        PENDING_BUYBACK, PENDING_KEEPER_BOUNTY, PENDING_LIQUIDITY, PENDING_PROVIDE,
+        PROVIDE_LIQUIDITY_REPLY_ID, REFLECTION_SWAP_REPLY_ID, ROUTE_PROBE_AMOUNT,
+        UNCLAIMED_DIVIDENDS,
+    };
+    use crate::msg::{
+        AssetHolding, BuybackConfig, BuybackResponse, ConfigResponse, Cw20HookMsg, Cw20ReceiveMsg,
+        DividendsResponse, ExecuteMsg, HoldingsResponse, InstantiateMsg, KeeperBounty,
+        KeeperBountyResponse, KeeperReward, LegStatus, LiquifyOutcome, LiquifyPreviewResponse,
+        LpBurnMode, LpLockResponse, PairConfig, QueryMsg, RoutesResponse, SlippageConfig,
+        StatsResponse, TokenExecuteMsg, TreasuryRatesResponse,
+    };
+    use choice::adapter::Dex;
+    use choice::asset::{Asset, AssetInfo, PairInfo};
+    use choice::pair::{Cw20HookMsg as PairCw20HookMsg, PoolResponse, SimulationResponse};
+    use choice::router::{
+        Cw20HookMsg as RouterCw20HookMsg, SimulateSwapOperationsResponse, SwapOperation,
+    };
+    use cosmwasm_std::testing::{
+        message_info, mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{
+        coin, from_json, to_json_binary, Addr, Binary, Coin, ContractResult, CosmosMsg, Decimal,
+        Env, OwnedDeps, QuerierResult, Reply, Response, SubMsg, SubMsgResponse, SubMsgResult,
+        SystemError, SystemResult, Timestamp, Uint128, WasmMsg, WasmQuery,
+    };
+    use cw20::{BalanceResponse, Cw20ExecuteMsg};
+    use cw20_base::ContractError;
+    use serde::de::DeserializeOwned;
+
+    const QUOTE: &str = "inj";
+
+    /// Balances and prices the mocked token, pairs and router answer from
+    #[derive(Clone, Default)]
+    struct Chain {
+        /// CW20 balances by (token, holder)
+        cw20: HashMap<(String, String), Uint128>,
+        pairs: HashMap<String, PairInfo>,
+        reflection_rate: Decimal,
+        burn_rate: Decimal,
+        /// Amount the pairs and the router return per unit offered
+        price: Decimal,
+        /// Share of a pair's return kept as commission
+        commission: Decimal,
+        pool: [Uint128; 2],
+        total_share: Uint128,
+        /// Makes every swap simulation fail
+        fail_simulations: bool,
+        /// Makes the router fail to simulate the amount routes are probed with
+        fail_route_probe: bool,
+        /// Pairs and routers speaking Astroport messages, the others speak Choice ones
+        astroport: HashSet<String>,
+    }
+
+    impl Chain {
+        fn answer(&self, query: &WasmQuery) -> QuerierResult {
+            let WasmQuery::Smart { contract_addr, msg } = query else {
+                return SystemResult::Err(SystemError::UnsupportedRequest {
+                    kind: "wasm".to_string(),
+                });
+            };
+            let msg: serde_json::Value = serde_json::from_slice(msg.as_slice()).unwrap();
+            let (kind, args) = msg.as_object().unwrap().iter().next().unwrap();
+            let amount =
+                |key: &str| -> Uint128 { serde_json::from_value(args[key].clone()).unwrap() };
+
+            // Pairs and routers only understand the queries of their own DEX
+            let astroport = self.astroport.contains(contract_addr);
+            let understood = match kind.as_str() {
+                "simulation" => args.get("ask_asset_info").is_some() == astroport,
+                "simulate_swap_operations" => {
+                    let operation = if astroport { "astro_swap" } else { "choice" };
+                    args["operations"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .all(|op| op.get(operation).is_some())
+                }
+                _ => true,
+            };
+            if !understood {
+                return SystemResult::Ok(ContractResult::Err(format!("unknown {} shape", kind)));
+            }
+
+            let response = match kind.as_str() {
+                "balance" => to_json_binary(&BalanceResponse {
+                    balance: self
+                        .cw20
+                        .get(&(
+                            contract_addr.clone(),
+                            args["address"].as_str().unwrap().to_string(),
+                        ))
+                        .copied()
+                        .unwrap_or_default(),
+                }),
+                "treasury_rates" => to_json_binary(&TreasuryRatesResponse {
+                    reflection_rate: self.reflection_rate,
+                    burn_rate: self.burn_rate,
+                }),
+                "pair" if astroport => {
+                    let pair = &self.pairs[contract_addr];
+                    to_json_binary(&serde_json::json!({
+                        "asset_infos": pair.asset_infos,
+                        "contract_addr": pair.contract_addr,
+                        "liquidity_token": pair.liquidity_token,
+                        "pair_type": { "xyk": {} },
+                    }))
+                }
+                "pair" => to_json_binary(&self.pairs[contract_addr]),
+                "pool" => {
+                    let asset_infos = &self.pairs[contract_addr].asset_infos;
+                    to_json_binary(&PoolResponse {
+                        assets: [
+                            Asset {
+                                info: asset_infos[0].clone(),
+                                amount: self.pool[0],
+                            },
+                            Asset {
+                                info: asset_infos[1].clone(),
+                                amount: self.pool[1],
+                            },
+                        ],
+                        total_share: self.total_share,
+                    })
+                }
+                "simulation" | "simulate_swap_operations" if self.fail_simulations => {
+                    return SystemResult::Ok(ContractResult::Err("no liquidity".to_string()))
+                }
+                "simulation" => {
+                    let offer: Asset = serde_json::from_value(args["offer_asset"].clone()).unwrap();
+                    let gross = offer.amount.mul_floor(self.price);
+                    let commission_amount = gross.mul_floor(self.commission);
+                    to_json_binary(&SimulationResponse {
+                        return_amount: gross - commission_amount,
+                        spread_amount: Uint128::zero(),
+                        commission_amount,
+                    })
+                }
+                "simulate_swap_operations"
+                    if self.fail_route_probe
+                        && amount("offer_amount") == Uint128::new(ROUTE_PROBE_AMOUNT) =>
+                {
+                    return SystemResult::Ok(ContractResult::Err("no route".to_string()))
+                }
+                "simulate_swap_operations" => to_json_binary(&SimulateSwapOperationsResponse {
+                    amount: amount("offer_amount").mul_floor(self.price),
+                }),
+                _ => {
+                    return SystemResult::Err(SystemError::UnsupportedRequest {
+                        kind: kind.clone(),
+                    })
+                }
+            };
+            SystemResult::Ok(ContractResult::Ok(response.unwrap()))
+        }
+    }
+
+    /// Treasury liquifying the token into the token/INJ pair and reflecting into a CW20 reward
+    /// through the token/INJ and reward/INJ pairs
+    struct Suite {
+        deps: OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        env: Env,
+        chain: Chain,
+        admin: Addr,
+        keeper: Addr,
+        token: Addr,
+        pair: Addr,
+        lp: Addr,
+        reward: Addr,
+    }
+
+    impl Suite {
+        fn new() -> Self {
+            let deps = mock_dependencies();
+            let env = mock_env();
+            let admin = deps.api.addr_make("admin");
+            let keeper = deps.api.addr_make("keeper");
+            let token = deps.api.addr_make("token");
+            let router = deps.api.addr_make("router");
+            let pair = deps.api.addr_make("pair");
+            let lp = deps.api.addr_make("lp");
+            let reflection_pair = deps.api.addr_make("reflection_pair");
+            let reward = deps.api.addr_make("reward");
+
+            let token_info = AssetInfo::Token {
+                contract_addr: token.to_string(),
+            };
+            let reward_info = AssetInfo::Token {
+                contract_addr: reward.to_string(),
+            };
+            let quote_info = AssetInfo::NativeToken {
+                denom: QUOTE.to_string(),
+            };
+            let mut chain = Chain {
+                reflection_rate: Decimal::percent(20),
+                burn_rate: Decimal::percent(10),
+                price: Decimal::percent(200),
+                pool: [Uint128::new(1_000_000), Uint128::new(2_000_000)],
+                total_share: Uint128::new(1_000_000),
+                ..Chain::default()
+            };
+            chain.pairs.insert(
+                pair.to_string(),
+                PairInfo {
+                    asset_infos: [token_info.clone(), quote_info.clone()],
+                    contract_addr: pair.to_string(),
+                    liquidity_token: lp.to_string(),
+                    asset_decimals: [6, 6],
+                },
+            );
+            chain.pairs.insert(
+                reflection_pair.to_string(),
+                PairInfo {
+                    asset_infos: [reward_info.clone(), quote_info.clone()],
+                    contract_addr: reflection_pair.to_string(),
+                    liquidity_token: deps.api.addr_make("reflection_lp").to_string(),
+                    asset_decimals: [6, 6],
+                },
+            );
+
+            let mut suite = Suite {
+                deps,
+                env,
+                chain,
+                admin,
+                keeper,
+                token,
+                pair,
+                lp,
+                reward,
+            };
+            suite.sync();
+
+            let info = message_info(&suite.admin, &[]);
+            instantiate(
+                suite.deps.as_mut(),
+                suite.env.clone(),
+                info,
+                InstantiateMsg {
+                    admin: suite.admin.to_string(),
+                    router: router.to_string(),
+                    token: suite.token.clone(),
+                },
+            )
+            .unwrap();
+            let admin = suite.admin.clone();
+            suite
+                .execute(
+                    &admin,
+                    ExecuteMsg::SetLiquidityPair {
+                        asset_infos: [token_info, quote_info.clone()],
+                        pair_contract: suite.pair.to_string(),
+                        dex: None,
+                    },
+                )
+                .unwrap();
+            suite
+                .execute(
+                    &admin,
+                    ExecuteMsg::SetReflectionPair {
+                        asset_infos: [reward_info, quote_info],
+                        pair_contract: reflection_pair.to_string(),
+                        dex: None,
+                    },
+                )
+                .unwrap();
+            suite
+        }
+
+        /// Answers the contract's queries from the current chain state
+        fn sync(&mut self) {
+            let chain = self.chain.clone();
+            self.deps
+                .querier
+                .update_wasm(move |query| chain.answer(query));
+        }
+
+        /// Sets the treasury's balance of a CW20
+        fn set_cw20(&mut self, token: &Addr, amount: u128) {
+            let treasury = self.env.contract.address.to_string();
+            self.chain
+                .cw20
+                .insert((token.to_string(), treasury), Uint128::new(amount));
+            self.sync();
+        }
+
+        /// Sets the treasury's balance of the quote asset
+        fn set_quote(&mut self, amount: u128) {
+            self.deps.querier.bank.update_balance(
+                self.env.contract.address.as_str(),
+                vec![coin(amount, QUOTE)],
+            );
+        }
+
+        fn execute(&mut self, sender: &Addr, msg: ExecuteMsg) -> Result<Response, ContractError> {
+            let info = message_info(sender, &[]);
+            execute(self.deps.as_mut(), self.env.clone(), info, msg)
+        }
+
+        fn liquify(&mut self) -> Response {
+            let keeper = self.keeper.clone();
+            self.execute(&keeper, ExecuteMsg::Liquify {}).unwrap()
+        }
+
+        /// Liquifies a balance of 100_000 and replies to its liquidity leg, the provide minting
+        /// `lp_minted`. Returns the reply to the provide.
+        fn liquify_providing(&mut self, lp_minted: u128) -> Response {
+            let (token, lp) = (self.token.clone(), self.lp.clone());
+            self.set_cw20(&token, 100_000);
+            self.liquify();
+            self.set_quote(70_000);
+            self.reply(LIQUIDITY_SWAP_REPLY_ID, None);
+            let lp_balance = self
+                .chain
+                .cw20
+                .get(&(lp.to_string(), self.env.contract.address.to_string()))
+                .copied()
+                .unwrap_or_default();
+            self.set_cw20(&lp, lp_balance.u128() + lp_minted);
+            self.reply(PROVIDE_LIQUIDITY_REPLY_ID, None)
+        }
+
+        /// Replies to a submessage, failing it with `error` when set
+        fn reply(&mut self, id: u64, error: Option<&str>) -> Response {
+            let result = match error {
+                Some(error) => SubMsgResult::Err(error.to_string()),
+                #[allow(deprecated)]
+                None => SubMsgResult::Ok(SubMsgResponse {
+                    events: vec![],
+                    data: None,
+                    msg_responses: vec![],
+                }),
+            };
+            reply(
+                self.deps.as_mut(),
+                self.env.clone(),
+                Reply {
+                    id,
+                    payload: Binary::default(),
+                    gas_used: 0,
+                    result,
+                },
+            )
+            .unwrap()
+        }
+
+        fn query<T: DeserializeOwned>(&self, msg: QueryMsg) -> T {
+            from_json(crate::contract::query(self.deps.as_ref(), self.env.clone(), msg).unwrap())
+                .unwrap()
+        }
+
+        fn last_liquify(&self) -> LiquifyOutcome {
+            self.query::<Option<LiquifyOutcome>>(QueryMsg::LastLiquify {})
+                .unwrap()
+        }
+
+        fn stats(&self) -> StatsResponse {
+            self.query(QueryMsg::Stats {})
+        }
+    }
+
+    /// Contract, decoded message and funds of a wasm execute
+    fn wasm_execute<T: DeserializeOwned>(msg: &SubMsg) -> (String, T, Vec<Coin>) {
+        match &msg.msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                msg,
+                funds,
+            }) => (
+                contract_addr.clone(),
+                from_json(msg).unwrap(),
+                funds.clone(),
+            ),
+            msg => panic!("Unexpected message {:?}", msg),
+        }
+    }
+
+    fn reply_ids(res: &Response) -> Vec<u64> {
+        res.messages.iter().map(|msg| msg.id).collect()
+    }
+
+    /// Whether a response tells the token the treasury liquified
+    fn reports_liquified(res: &Response) -> bool {
+        res.messages.iter().any(|msg| {
+            matches!(
+                &msg.msg,
+                CosmosMsg::Wasm(WasmMsg::Execute { msg, .. })
+                    if from_json::<TokenExecuteMsg>(msg).ok()
+                        == Some(TokenExecuteMsg::TreasuryLiquified {})
+            )
+        })
+    }
+
+    #[test]
+    fn test_provide_allowance_revoked_on_failure() {
+        let mut suite = Suite::new();
+        let token = suite.token.clone();
+        suite.set_cw20(&token, 100_000);
+
+        // 20% reflected, 10% burned, the 70% left is half swapped into INJ and provided
+        let res = suite.liquify();
+        assert_eq!(
+            reply_ids(&res),
+            vec![
+                LIQUIDITY_SWAP_REPLY_ID,
+                REFLECTION_SWAP_REPLY_ID,
+                BURN_REPLY_ID
+            ]
+        );
+
+        suite.set_quote(70_000);
+        let res = suite.reply(LIQUIDITY_SWAP_REPLY_ID, None);
+        assert_eq!(reply_ids(&res), vec![0, PROVIDE_LIQUIDITY_REPLY_ID]);
+        let (contract, msg, _) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0]);
+        assert_eq!(contract, token.to_string());
+        assert_eq!(
+            msg,
+            Cw20ExecuteMsg::IncreaseAllowance {
+                spender: suite.pair.to_string(),
+                amount: Uint128::new(35_000),
+                expires: None,
+            }
+        );
+        let (contract, _, funds) = wasm_execute::<serde_json::Value>(&res.messages[1]);
+        assert_eq!(contract, suite.pair.to_string());
+        assert_eq!(funds, vec![coin(70_000, QUOTE)]);
+        assert_eq!(
+            PENDING_PROVIDE.load(&suite.deps.storage).unwrap(),
+            PendingProvide {
+                spender: suite.pair.to_string(),
+                allowances: vec![Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: token.to_string(),
+                    },
+                    amount: Uint128::new(35_000),
+                }],
+            }
+        );
+
+        // The failed provide is reverted, the allowance granted before it is not
+        let res = suite.reply(PROVIDE_LIQUIDITY_REPLY_ID, Some("slippage exceeded"));
+        assert_eq!(res.messages.len(), 1);
+        let (contract, msg, _) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0]);
+        assert_eq!(contract, token.to_string());
+        assert_eq!(
+            msg,
+            Cw20ExecuteMsg::DecreaseAllowance {
+                spender: suite.pair.to_string(),
+                amount: Uint128::new(35_000),
+                expires: None,
+            }
+        );
+        assert!(!PENDING_PROVIDE.exists(&suite.deps.storage));
+        assert_eq!(
+            suite.last_liquify().liquidity.status,
+            LegStatus::Failed {
+                error: "slippage exceeded".to_string()
+            }
+        );
+        assert_eq!(suite.stats().total_lp_minted, Uint128::zero());
+    }
+
+    #[test]
+    fn test_provide_records_lp_minted() {
+        let mut suite = Suite::new();
+        let (token, lp) = (suite.token.clone(), suite.lp.clone());
+        suite.set_cw20(&token, 100_000);
+        suite.set_cw20(&lp, 500);
+
+        suite.liquify();
+        suite.set_quote(70_000);
+        suite.reply(LIQUIDITY_SWAP_REPLY_ID, None);
+
+        suite.set_cw20(&lp, 2_000);
+        let res = suite.reply(PROVIDE_LIQUIDITY_REPLY_ID, None);
+        assert_eq!(res.messages.len(), 1);
+        assert!(reports_liquified(&res));
+        assert!(!PENDING_PROVIDE.exists(&suite.deps.storage));
+
+        let stats = suite.stats();
+        assert_eq!(stats.total_lp_minted, Uint128::new(1_500));
+        assert_eq!(stats.total_liquified, Uint128::new(70_000));
+        assert_eq!(
+            suite.last_liquify().liquidity.status,
+            LegStatus::Succeeded {}
+        );
+    }
+
+    #[test]
+    fn test_liquify_leg_outcomes() {
+        let mut suite = Suite::new();
+        let (token, reward) = (suite.token.clone(), suite.reward.clone());
+        suite.set_cw20(&token, 100_000);
+        suite.set_cw20(&reward, 1_000);
+
+        suite.liquify();
+        let outcome = suite.last_liquify();
+        assert_eq!(outcome.height, suite.env.block.height);
+        assert_eq!(outcome.liquidity.amount, Uint128::new(70_000));
+        assert_eq!(outcome.reflection.amount, Uint128::new(20_000));
+        assert_eq!(outcome.burn.amount, Uint128::new(10_000));
+        for leg in [outcome.liquidity, outcome.reflection, outcome.burn] {
+            assert_eq!(leg.status, LegStatus::Pending {});
+        }
+
+        // Each leg replies on its own, the failed ones keep their funds in the treasury
+        // The token restarts its split once the first leg succeeds, not when one fails
+        let res = suite.reply(LIQUIDITY_SWAP_REPLY_ID, Some("swap failed"));
+        assert!(res.messages.is_empty());
+        assert!(!PENDING_LIQUIDITY.exists(&suite.deps.storage));
+        suite.set_cw20(&reward, 41_000);
+        let res = suite.reply(REFLECTION_SWAP_REPLY_ID, None);
+        assert_eq!(res.messages.len(), 1);
+        assert!(reports_liquified(&res));
+        let res = suite.reply(BURN_REPLY_ID, Some("burn failed"));
+        assert!(!reports_liquified(&res));
+
+        let outcome = suite.last_liquify();
+        assert_eq!(
+            outcome.liquidity.status,
+            LegStatus::Failed {
+                error: "swap failed".to_string()
+            }
+        );
+        assert_eq!(outcome.reflection.status, LegStatus::Succeeded {});
+        assert_eq!(
+            outcome.burn.status,
+            LegStatus::Failed {
+                error: "burn failed".to_string()
+            }
+        );
+
+        let stats = suite.stats();
+        assert_eq!(stats.liquify_runs, 1);
+        assert_eq!(stats.total_liquified, Uint128::new(20_000));
+        assert_eq!(stats.total_reward_acquired, Uint128::new(40_000));
+    }
+
+    #[test]
+    fn test_liquify_failed_simulations() {
+        let mut suite = Suite::new();
+        let token = suite.token.clone();
+        suite.set_cw20(&token, 100_000);
+        suite.chain.fail_simulations = true;
+        suite.sync();
+
+        // Only the burn is dispatched, the legs that cannot be simulated fail without reverting it
+        let res = suite.liquify();
+        assert_eq!(reply_ids(&res), vec![BURN_REPLY_ID]);
+        let outcome = suite.last_liquify();
+        for leg in [&outcome.liquidity, &outcome.reflection] {
+            match &leg.status {
+                LegStatus::Failed { error } => assert!(error.contains("no liquidity")),
+                status => panic!("Unexpected status {:?}", status),
+            }
+        }
+        assert_eq!(outcome.burn.status, LegStatus::Pending {});
+        assert!(!PENDING_LIQUIDITY.exists(&suite.deps.storage));
+    }
+
+    #[test]
+    fn test_liquidity_swap_returning_nothing() {
+        let mut suite = Suite::new();
+        let token = suite.token.clone();
+        suite.set_cw20(&token, 100_000);
+
+        suite.liquify();
+        let res = suite.reply(LIQUIDITY_SWAP_REPLY_ID, None);
+        assert!(res.messages.is_empty());
+        assert_eq!(
+            suite.last_liquify().liquidity.status,
+            LegStatus::Failed {
+                error: "Liquidity swap returned nothing".to_string()
+            }
+        );
+        assert_eq!(suite.stats().total_liquified, Uint128::zero());
+    }
+
+    #[test]
+    fn test_slippage_validation() {
+        let mut suite = Suite::new();
+        let (admin, keeper) = (suite.admin.clone(), suite.keeper.clone());
+        let slippage = |max_spread: u64, slippage_tolerance: u64, deadline_seconds: u64| {
+            ExecuteMsg::SetSlippage {
+                config: SlippageConfig {
+                    max_spread: Decimal::percent(max_spread),
+                    slippage_tolerance: Decimal::percent(slippage_tolerance),
+                    deadline_seconds,
+                },
+            }
+        };
+
+        let err = suite.execute(&keeper, slippage(3, 2, 60)).unwrap_err();
+        assert!(err.to_string().contains("not admin"));
+        for msg in [slippage(101, 2, 60), slippage(3, 101, 60)] {
+            let err = suite.execute(&admin, msg).unwrap_err();
+            assert!(err.to_string().contains("must be <= 1"));
+        }
+        let err = suite.execute(&admin, slippage(3, 2, 0)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("deadline_seconds must be greater than 0"));
+        assert_eq!(
+            suite.query::<SlippageConfig>(QueryMsg::Slippage {}),
+            SlippageConfig::default()
+        );
+
+        suite.execute(&admin, slippage(100, 100, 1)).unwrap();
+        assert_eq!(
+            suite.query::<SlippageConfig>(QueryMsg::Slippage {}),
+            SlippageConfig {
+                max_spread: Decimal::one(),
+                slippage_tolerance: Decimal::one(),
+                deadline_seconds: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_swap_hooks_carry_slippage_bounds() {
+        let mut suite = Suite::new();
+        let (admin, token) = (suite.admin.clone(), suite.token.clone());
+        let router = suite.deps.api.addr_make("router");
+        suite.chain.commission = Decimal::percent(1);
+        suite.set_cw20(&token, 100_000);
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::SetSlippage {
+                    config: SlippageConfig {
+                        max_spread: Decimal::percent(3),
+                        slippage_tolerance: Decimal::percent(2),
+                        deadline_seconds: 60,
+                    },
+                },
+            )
+            .unwrap();
+        let deadline = suite.env.block.time.seconds() + 60;
+
+        let res = suite.liquify();
+        let send = |id: u64| {
+            let msg = res.messages.iter().find(|msg| msg.id == id).unwrap();
+            match wasm_execute::<Cw20ExecuteMsg>(msg) {
+                (_, Cw20ExecuteMsg::Send { contract, msg, .. }, _) => (contract, msg),
+                (_, msg, _) => panic!("Unexpected message {:?}", msg),
+            }
+        };
+
+        // 35_000 simulates to 70_000 before the commission, the belief price excludes it
+        let (contract, hook) = send(LIQUIDITY_SWAP_REPLY_ID);
+        assert_eq!(contract, suite.pair.to_string());
+        assert_eq!(
+            from_json::<PairCw20HookMsg>(&hook).unwrap(),
+            PairCw20HookMsg::Swap {
+                belief_price: Some(Decimal::percent(50)),
+                max_spread: Some(Decimal::percent(3)),
+                to: None,
+                deadline: Some(deadline),
+            }
+        );
+
+        // The router simulates 20_000 to 40_000, of which 3% may be lost
+        let (contract, hook) = send(REFLECTION_SWAP_REPLY_ID);
+        assert_eq!(contract, router.to_string());
+        let quote_info = AssetInfo::NativeToken {
+            denom: QUOTE.to_string(),
+        };
+        assert_eq!(
+            from_json::<RouterCw20HookMsg>(&hook).unwrap(),
+            RouterCw20HookMsg::ExecuteSwapOperations {
+                operations: vec![
+                    SwapOperation::Choice {
+                        offer_asset_info: AssetInfo::Token {
+                            contract_addr: token.to_string(),
+                        },
+                        ask_asset_info: quote_info.clone(),
+                    },
+                    SwapOperation::Choice {
+                        offer_asset_info: quote_info,
+                        ask_asset_info: AssetInfo::Token {
+                            contract_addr: suite.reward.to_string(),
+                        },
+                    },
+                ],
+                minimum_receive: Some(Uint128::new(38_800)),
+                to: None,
+                deadline: Some(deadline),
+            }
+        );
+
+        // The provide carries the slippage tolerance and the same deadline
+        suite.set_quote(69_300);
+        let res = suite.reply(LIQUIDITY_SWAP_REPLY_ID, None);
+        let provide = res
+            .messages
+            .iter()
+            .find(|msg| msg.id == PROVIDE_LIQUIDITY_REPLY_ID)
+            .unwrap();
+        let (_, msg, _) = wasm_execute::<serde_json::Value>(provide);
+        let provide = &msg["provide_liquidity"];
+        assert_eq!(provide["slippage_tolerance"], "0.02");
+        assert_eq!(provide["deadline"], deadline);
+    }
+
+    /// Router hop from `offer` to `ask`
+    fn hop(offer: &AssetInfo, ask: &AssetInfo) -> SwapOperation {
+        SwapOperation::Choice {
+            offer_asset_info: offer.clone(),
+            ask_asset_info: ask.clone(),
+        }
+    }
+
+    #[test]
+    fn test_route_validation() {
+        let mut suite = Suite::new();
+        let admin = suite.admin.clone();
+        let cw20 = |addr: &Addr| AssetInfo::Token {
+            contract_addr: addr.to_string(),
+        };
+        let token = cw20(&suite.token);
+        let reward = cw20(&suite.reward);
+        let other = cw20(&suite.deps.api.addr_make("other"));
+        let quote = AssetInfo::NativeToken {
+            denom: QUOTE.to_string(),
+        };
+        let reflection = |operations: Vec<SwapOperation>| ExecuteMsg::SetReflectionRoute {
+            operations: Some(operations),
+        };
+        let liquidity = |operations: Vec<SwapOperation>| ExecuteMsg::SetLiquidityRoute {
+            operations: Some(operations),
+        };
+
+        for (msg, error) in [
+            (reflection(vec![]), "at least one hop"),
+            (
+                reflection(vec![hop(&quote, &reward)]),
+                "start from the reflection token",
+            ),
+            (
+                liquidity(vec![hop(&reward, &quote)]),
+                "start from the reflection token",
+            ),
+            (
+                reflection(vec![hop(&token, &quote), hop(&reward, &other)]),
+                "do not connect",
+            ),
+            (
+                reflection(vec![hop(&token, &quote), hop(&quote, &token)]),
+                "cannot end in the reflection token",
+            ),
+            (
+                liquidity(vec![hop(&token, &quote), hop(&quote, &reward)]),
+                "quote asset of the liquidity pair",
+            ),
+        ] {
+            let err = suite.execute(&admin, msg).unwrap_err();
+            assert!(err.to_string().contains(error), "{}", err);
+        }
+
+        // Unclaimed dividends pin the reward asset the reflection route ends in
+        DIVIDEND_ASSET
+            .save(&mut suite.deps.storage, &reward)
+            .unwrap();
+        UNCLAIMED_DIVIDENDS
+            .save(&mut suite.deps.storage, &Uint128::new(1_000))
+            .unwrap();
+        let err = suite
+            .execute(
+                &admin,
+                reflection(vec![hop(&token, &quote), hop(&quote, &other)]),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("dividends are unclaimed"));
+        suite
+            .execute(
+                &admin,
+                reflection(vec![hop(&token, &quote), hop(&quote, &reward)]),
+            )
+            .unwrap();
+
+        // The router must simulate the route with the probe amount and return something
+        suite.chain.fail_route_probe = true;
+        suite.sync();
+        let err = suite
+            .execute(&admin, liquidity(vec![hop(&token, &quote)]))
+            .unwrap_err();
+        assert!(err.to_string().contains("no route"));
+        suite.chain.fail_route_probe = false;
+        suite.chain.price = Decimal::zero();
+        suite.sync();
+        let err = suite
+            .execute(&admin, liquidity(vec![hop(&token, &quote)]))
+            .unwrap_err();
+        assert!(err.to_string().contains("Route returns nothing"));
+
+        suite.chain.price = Decimal::percent(200);
+        suite.sync();
+        suite
+            .execute(&admin, liquidity(vec![hop(&token, &quote)]))
+            .unwrap();
+        assert_eq!(
+            suite.query::<RoutesResponse>(QueryMsg::Routes {}),
+            RoutesResponse {
+                reflection: Some(vec![hop(&token, &quote), hop(&quote, &reward)]),
+                liquidity: Some(vec![hop(&token, &quote)]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_set_router_rechecks_routes() {
+        let mut suite = Suite::new();
+        let (admin, keeper) = (suite.admin.clone(), suite.keeper.clone());
+        let token = AssetInfo::Token {
+            contract_addr: suite.token.to_string(),
+        };
+        let quote = AssetInfo::NativeToken {
+            denom: QUOTE.to_string(),
+        };
+        let new_router = suite.deps.api.addr_make("new_router");
+        let set_router = ExecuteMsg::SetRouter {
+            router: new_router.to_string(),
+            dex: None,
+        };
+
+        let err = suite.execute(&keeper, set_router.clone()).unwrap_err();
+        assert!(err.to_string().contains("not admin"));
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::SetLiquidityRoute {
+                    operations: Some(vec![hop(&token, &quote)]),
+                },
+            )
+            .unwrap();
+
+        // A router that cannot simulate the stored route is refused
+        suite.chain.fail_route_probe = true;
+        suite.sync();
+        let err = suite.execute(&admin, set_router.clone()).unwrap_err();
+        assert!(err.to_string().contains("no route"));
+
+        // Without stored routes there is nothing to check
+        suite
+            .execute(&admin, ExecuteMsg::SetLiquidityRoute { operations: None })
+            .unwrap();
+        suite.execute(&admin, set_router).unwrap();
+        let config: ConfigResponse = suite.query(QueryMsg::Config {});
+        assert_eq!(config.router, new_router.to_string());
+    }
+
+    #[test]
+    fn test_liquify_through_astroport() {
+        let mut suite = Suite::new();
+        let (admin, token) = (suite.admin.clone(), suite.token.clone());
+        let astro_pair = suite.deps.api.addr_make("astro_pair");
+        let astro_lp = suite.deps.api.addr_make("astro_lp");
+        let astro_router = suite.deps.api.addr_make("astro_router");
+        let reflection_pair = suite.deps.api.addr_make("reflection_pair");
+        let token_info = AssetInfo::Token {
+            contract_addr: token.to_string(),
+        };
+        let reward_info = AssetInfo::Token {
+            contract_addr: suite.reward.to_string(),
+        };
+        let quote_info = AssetInfo::NativeToken {
+            denom: QUOTE.to_string(),
+        };
+        suite.chain.pairs.insert(
+            astro_pair.to_string(),
+            PairInfo {
+                asset_infos: [token_info.clone(), quote_info.clone()],
+                contract_addr: astro_pair.to_string(),
+                liquidity_token: astro_lp.to_string(),
+                asset_decimals: [6, 6],
+            },
+        );
+        for contract in [&astro_pair, &astro_router, &reflection_pair] {
+            suite.chain.astroport.insert(contract.to_string());
+        }
+        suite.set_cw20(&token, 100_000);
+
+        for msg in [
+            ExecuteMsg::SetLiquidityPair {
+                asset_infos: [token_info.clone(), quote_info.clone()],
+                pair_contract: astro_pair.to_string(),
+                dex: Some(Dex::Astroport),
+            },
+            ExecuteMsg::SetReflectionPair {
+                asset_infos: [reward_info.clone(), quote_info.clone()],
+                pair_contract: reflection_pair.to_string(),
+                dex: Some(Dex::Astroport),
+            },
+            ExecuteMsg::SetRouter {
+                router: astro_router.to_string(),
+                dex: Some(Dex::Astroport),
+            },
+        ] {
+            suite.execute(&admin, msg).unwrap();
+        }
+        let config: ConfigResponse = suite.query(QueryMsg::Config {});
+        assert_eq!(config.liquidity_token, Some(astro_lp.to_string()));
+        assert_eq!(config.router_dex, Dex::Astroport);
+        assert_eq!(config.liquidity_pair.unwrap().dex, Dex::Astroport);
+        assert_eq!(config.reflection_pair.unwrap().dex, Dex::Astroport);
+
+        // The preview reads the Astroport pool to estimate the LP minted
+        let preview: Option<LiquifyPreviewResponse> =
+            suite.query(QueryMsg::LiquifyPreview { keeper: None });
+        let preview = preview.unwrap();
+        assert_eq!(preview.liquidity.expected, Some(Uint128::new(70_000)));
+        assert_eq!(preview.expected_lp, Some(Uint128::new(33_816)));
+
+        // Astroport hooks carry no deadline, the router takes the max spread instead
+        let res = suite.liquify();
+        let hook = |id: u64| {
+            let msg = res.messages.iter().find(|msg| msg.id == id).unwrap();
+            match wasm_execute::<Cw20ExecuteMsg>(msg) {
+                (_, Cw20ExecuteMsg::Send { contract, msg, .. }, _) => {
+                    (contract, from_json::<serde_json::Value>(msg).unwrap())
+                }
+                (_, msg, _) => panic!("Unexpected message {:?}", msg),
+            }
+        };
+        assert_eq!(
+            hook(LIQUIDITY_SWAP_REPLY_ID),
+            (
+                astro_pair.to_string(),
+                serde_json::json!({
+                    "swap": {
+                        "ask_asset_info": null,
+                        "belief_price": "0.5",
+                        "max_spread": "0.05",
+                        "to": null,
+                    }
+                })
+            )
+        );
+        let astro_swap = |offer: &AssetInfo, ask: &AssetInfo| {
+            serde_json::json!({
+                "astro_swap": { "offer_asset_info": offer, "ask_asset_info": ask }
+            })
+        };
+        assert_eq!(
+            hook(REFLECTION_SWAP_REPLY_ID),
+            (
+                astro_router.to_string(),
+                serde_json::json!({
+                    "execute_swap_operations": {
+                        "operations": [
+                            astro_swap(&token_info, &quote_info),
+                            astro_swap(&quote_info, &reward_info),
+                        ],
+                        "minimum_receive": "38000",
+                        "to": null,
+                        "max_spread": "0.05",
+                    }
+                })
+            )
+        );
+
+        suite.set_quote(70_000);
+        let res = suite.reply(LIQUIDITY_SWAP_REPLY_ID, None);
+        let provide = res
+            .messages
+            .iter()
+            .find(|msg| msg.id == PROVIDE_LIQUIDITY_REPLY_ID)
+            .unwrap();
+        let (contract, msg, funds) = wasm_execute::<serde_json::Value>(provide);
+        assert_eq!(contract, astro_pair.to_string());
+        assert_eq!(funds, vec![coin(70_000, QUOTE)]);
+        assert_eq!(
+            msg,
+            serde_json::json!({
+                "provide_liquidity": {
+                    "assets": [
+                        Asset {
+                            info: token_info,
+                            amount: Uint128::new(35_000),
+                        },
+                        Asset {
+                            info: quote_info,
+                            amount: Uint128::new(70_000),
+                        },
+                    ],
+                    "slippage_tolerance": "0.05",
+                    "auto_stake": null,
+                    "receiver": null,
+                }
+            })
+        );
+
+        // The minted Astroport LP is measured like any other
+        suite.set_cw20(&astro_lp, 33_816);
+        suite.reply(PROVIDE_LIQUIDITY_REPLY_ID, None);
+        assert_eq!(suite.stats().total_lp_minted, Uint128::new(33_816));
+        assert_eq!(
+            suite.last_liquify().liquidity.status,
+            LegStatus::Succeeded {}
+        );
+    }
+
+    #[test]
+    fn test_lp_lock_minimum() {
+        let mut suite = Suite::new();
+        let admin = suite.admin.clone();
+        let now = suite.env.block.time.seconds();
+        let lock = |unlock_time: u64, release_duration: u64| ExecuteMsg::SetLpLock {
+            unlock_time,
+            release_duration,
+        };
+
+        for unlock_time in [now - 1, now, now + MIN_LP_LOCK_SECONDS - 1] {
+            let err = suite.execute(&admin, lock(unlock_time, 0)).unwrap_err();
+            assert!(err.to_string().contains("Unlock time must be at least"));
+        }
+        let unlock_time = now + MIN_LP_LOCK_SECONDS;
+        suite.execute(&admin, lock(unlock_time, 1_000)).unwrap();
+
+        // Once set, the lock can only be extended
+        for (unlock_time, release_duration) in [(unlock_time - 1, 1_000), (unlock_time, 999)] {
+            let err = suite
+                .execute(&admin, lock(unlock_time, release_duration))
+                .unwrap_err();
+            assert!(err.to_string().contains("can only be extended"));
+        }
+        suite.execute(&admin, lock(unlock_time + 1, 2_000)).unwrap();
+    }
+
+    #[test]
+    fn test_lp_lock_linear_release() {
+        let mut suite = Suite::new();
+        let (admin, lp) = (suite.admin.clone(), suite.lp.clone());
+        suite.set_cw20(&lp, 1_000);
+        let unlock_time = suite.env.block.time.seconds() + MIN_LP_LOCK_SECONDS;
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::SetLpLock {
+                    unlock_time,
+                    release_duration: 1_000,
+                },
+            )
+            .unwrap();
+
+        // Nothing is released at the unlock time itself
+        suite.env.block.time = Timestamp::from_seconds(unlock_time);
+        let lock: LpLockResponse = suite.query(QueryMsg::LpLock {});
+        assert_eq!(lock.withdrawable, Uint128::zero());
+        assert_eq!(lock.locked, Uint128::new(1_000));
+        assert_eq!(lock.release_end, Some(unlock_time + 1_000));
+        let err = suite
+            .execute(&admin, ExecuteMsg::WithdrawLp { amount: None })
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeds the released LP"));
+
+        suite.env.block.time = Timestamp::from_seconds(unlock_time + 250);
+        let res = suite
+            .execute(&admin, ExecuteMsg::WithdrawLp { amount: None })
+            .unwrap();
+        let (contract, msg, _) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0]);
+        assert_eq!(contract, lp.to_string());
+        assert_eq!(
+            msg,
+            Cw20ExecuteMsg::Transfer {
+                recipient: admin.to_string(),
+                amount: Uint128::new(250),
+            }
+        );
+        suite.set_cw20(&lp, 750);
+
+        // Halfway, half of everything held so far is released, withdrawals included
+        suite.env.block.time = Timestamp::from_seconds(unlock_time + 500);
+        let lock: LpLockResponse = suite.query(QueryMsg::LpLock {});
+        assert_eq!(lock.withdrawn, Uint128::new(250));
+        assert_eq!(lock.withdrawable, Uint128::new(250));
+        assert_eq!(lock.locked, Uint128::new(500));
+        let err = suite
+            .execute(
+                &admin,
+                ExecuteMsg::WithdrawLp {
+                    amount: Some(Uint128::new(251)),
+                },
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeds the released LP"));
+
+        suite.env.block.time = Timestamp::from_seconds(unlock_time + 1_000);
+        let lock: LpLockResponse = suite.query(QueryMsg::LpLock {});
+        assert_eq!(lock.withdrawable, Uint128::new(750));
+        assert_eq!(lock.locked, Uint128::zero());
+    }
+
+    #[test]
+    fn test_lp_withdrawn_reset_on_pair_change() {
+        let mut suite = Suite::new();
+        let (admin, lp) = (suite.admin.clone(), suite.lp.clone());
+        suite.set_cw20(&lp, 1_000);
+        let unlock_time = suite.env.block.time.seconds() + MIN_LP_LOCK_SECONDS;
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::SetLpLock {
+                    unlock_time,
+                    release_duration: 0,
+                },
+            )
+            .unwrap();
+
+        let new_pair = suite.deps.api.addr_make("new_pair");
+        let new_lp = suite.deps.api.addr_make("new_lp");
+        let mut pair_info = suite.chain.pairs[suite.pair.as_str()].clone();
+        pair_info.contract_addr = new_pair.to_string();
+        pair_info.liquidity_token = new_lp.to_string();
+        suite
+            .chain
+            .pairs
+            .insert(new_pair.to_string(), pair_info.clone());
+        suite.sync();
+        let move_pair = ExecuteMsg::SetLiquidityPair {
+            asset_infos: pair_info.asset_infos,
+            pair_contract: new_pair.to_string(),
+            dex: None,
+        };
+
+        // The locked LP cannot be left behind
+        let err = suite.execute(&admin, move_pair.clone()).unwrap_err();
+        assert!(err.to_string().contains("holding locked LP"));
+
+        suite.env.block.time = Timestamp::from_seconds(unlock_time);
+        suite
+            .execute(&admin, ExecuteMsg::WithdrawLp { amount: None })
+            .unwrap();
+        suite.set_cw20(&lp, 0);
+        let lock: LpLockResponse = suite.query(QueryMsg::LpLock {});
+        assert_eq!(lock.withdrawn, Uint128::new(1_000));
+
+        // The withdrawals of the old LP token do not release the new one
+        suite.execute(&admin, move_pair).unwrap();
+        suite.set_cw20(&new_lp, 400);
+        let lock: LpLockResponse = suite.query(QueryMsg::LpLock {});
+        assert_eq!(lock.lp_token, Some(new_lp.to_string()));
+        assert_eq!(lock.withdrawn, Uint128::zero());
+        assert_eq!(lock.withdrawable, Uint128::new(400));
+    }
+
+    #[test]
+    fn test_lp_burn_modes() {
+        let dead = MockApi::default().addr_make("dead");
+        for (mode, expected) in [
+            (
+                LpBurnMode::Burn {},
+                Cw20ExecuteMsg::Burn {
+                    amount: Uint128::new(1_500),
+                },
+            ),
+            (
+                LpBurnMode::Send {
+                    recipient: dead.to_string(),
+                },
+                Cw20ExecuteMsg::Transfer {
+                    recipient: dead.to_string(),
+                    amount: Uint128::new(1_500),
+                },
+            ),
+        ] {
+            let mut suite = Suite::new();
+            let (admin, lp) = (suite.admin.clone(), suite.lp.clone());
+            suite
+                .execute(&admin, ExecuteMsg::SetLpBurn { mode: Some(mode) })
+                .unwrap();
+
+            // Only the minted LP leaves the treasury, what it already held stays
+            suite.set_cw20(&lp, 500);
+            let res = suite.liquify_providing(1_500);
+            assert_eq!(res.messages.len(), 2);
+            assert!(reports_liquified(&res));
+            let (contract, msg, _) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0]);
+            assert_eq!(contract, lp.to_string());
+            assert_eq!(msg, expected);
+
+            let stats = suite.stats();
+            assert_eq!(stats.total_lp_minted, Uint128::new(1_500));
+            assert_eq!(stats.total_lp_burned, Uint128::new(1_500));
+        }
+    }
+
+    #[test]
+    fn test_lp_burn_mode_config() {
+        let mut suite = Suite::new();
+        let admin = suite.admin.clone();
+        let err = suite
+            .execute(
+                &admin,
+                ExecuteMsg::SetLpBurn {
+                    mode: Some(LpBurnMode::Send {
+                        recipient: "not an address".to_string(),
+                    }),
+                },
+            )
+            .unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)));
+        let keeper = suite.keeper.clone();
+        let err = suite
+            .execute(
+                &keeper,
+                ExecuteMsg::SetLpBurn {
+                    mode: Some(LpBurnMode::Burn {}),
+                },
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("not admin"));
+
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::SetLpBurn {
+                    mode: Some(LpBurnMode::Burn {}),
+                },
+            )
+            .unwrap();
+        suite
+            .execute(&admin, ExecuteMsg::SetLpBurn { mode: None })
+            .unwrap();
+        assert_eq!(suite.query::<Option<LpBurnMode>>(QueryMsg::LpBurn {}), None);
+
+        // Without a burn mode the minted LP is kept
+        let res = suite.liquify_providing(1_500);
+        assert_eq!(res.messages.len(), 1);
+        assert!(reports_liquified(&res));
+        assert_eq!(suite.stats().total_lp_burned, Uint128::zero());
+    }
+
+    /// Enables buybacks of up to 50_000 INJ per hour
+    fn set_buyback(suite: &mut Suite, on_liquify: bool) {
+        let admin = suite.admin.clone();
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::SetBuyback {
+                    config: Some(BuybackConfig {
+                        max_per_run: Uint128::new(50_000),
+                        max_spread: Decimal::percent(10),
+                        interval_seconds: 3_600,
+                        on_liquify,
+                    }),
+                },
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_buyback_burns_swap_return() {
+        let mut suite = Suite::new();
+        let (keeper, token) = (suite.keeper.clone(), suite.token.clone());
+        set_buyback(&mut suite, false);
+        suite.set_quote(80_000);
+        suite.set_cw20(&token, 1_000);
+
+        let res = suite.execute(&keeper, ExecuteMsg::Buyback {}).unwrap();
+        assert_eq!(reply_ids(&res), vec![BUYBACK_SWAP_REPLY_ID]);
+        let (contract, _, funds) = wasm_execute::<serde_json::Value>(&res.messages[0]);
+        assert_eq!(contract, suite.pair.to_string());
+        assert_eq!(funds, vec![coin(50_000, QUOTE)]);
+
+        // Only what the swap returned is burned
+        suite.set_quote(30_000);
+        suite.set_cw20(&token, 101_000);
+        let res = suite.reply(BUYBACK_SWAP_REPLY_ID, None);
+        let (contract, msg, _) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0]);
+        assert_eq!(contract, token.to_string());
+        assert_eq!(
+            msg,
+            Cw20ExecuteMsg::Burn {
+                amount: Uint128::new(100_000)
+            }
+        );
+        assert!(!PENDING_BUYBACK.exists(&suite.deps.storage));
+        let stats = suite.stats();
+        assert_eq!(stats.total_buyback_spent, Uint128::new(50_000));
+        assert_eq!(stats.total_buyback_burned, Uint128::new(100_000));
+
+        let now = suite.env.block.time.seconds();
+        let buyback: BuybackResponse = suite.query(QueryMsg::Buyback {});
+        assert_eq!(buyback.last_run, Some(now));
+        assert_eq!(buyback.next_run, Some(now + 3_600));
+        assert_eq!(buyback.available, Uint128::new(30_000));
+        let err = suite.execute(&keeper, ExecuteMsg::Buyback {}).unwrap_err();
+        assert!(err.to_string().contains("Next buyback at"));
+    }
+
+    #[test]
+    fn test_buyback_swap_failure() {
+        let mut suite = Suite::new();
+        let keeper = suite.keeper.clone();
+        set_buyback(&mut suite, false);
+        suite.set_quote(80_000);
+
+        suite.execute(&keeper, ExecuteMsg::Buyback {}).unwrap();
+        let res = suite.reply(BUYBACK_SWAP_REPLY_ID, Some("max spread exceeded"));
+        assert!(res.messages.is_empty());
+        assert!(!PENDING_BUYBACK.exists(&suite.deps.storage));
+        let stats = suite.stats();
+        assert_eq!(stats.total_buyback_spent, Uint128::zero());
+        assert_eq!(stats.total_buyback_burned, Uint128::zero());
+
+        // The failed swap still counts as the run of its interval
+        let buyback: BuybackResponse = suite.query(QueryMsg::Buyback {});
+        assert_eq!(buyback.last_run, Some(suite.env.block.time.seconds()));
+    }
+
+    #[test]
+    fn test_buyback_on_liquify_failure() {
+        let mut suite = Suite::new();
+        let token = suite.token.clone();
+        set_buyback(&mut suite, true);
+        suite.set_cw20(&token, 100_000);
+
+        // The buyback runs after the legs and its failure only reaches its own reply
+        let res = suite.liquify();
+        assert_eq!(
+            reply_ids(&res),
+            vec![
+                LIQUIDITY_SWAP_REPLY_ID,
+                REFLECTION_SWAP_REPLY_ID,
+                BURN_REPLY_ID,
+                BUYBACK_TRIGGER_REPLY_ID
+            ]
+        );
+        let (contract, msg, _) = wasm_execute::<ExecuteMsg>(&res.messages[3]);
+        assert_eq!(contract, suite.env.contract.address.to_string());
+        assert_eq!(msg, ExecuteMsg::Buyback {});
+
+        let res = suite.reply(
+            BUYBACK_TRIGGER_REPLY_ID,
+            Some("No quote asset to buy back with"),
+        );
+        assert!(res.messages.is_empty());
+        let now = suite.env.block.time.seconds();
+        let buyback: BuybackResponse = suite.query(QueryMsg::Buyback {});
+        assert_eq!(buyback.last_run, Some(now));
+
+        // The next liquify does not retry it before the interval
+        suite.env.block.time = Timestamp::from_seconds(now + 3_599);
+        let res = suite.liquify();
+        assert!(!reply_ids(&res).contains(&BUYBACK_TRIGGER_REPLY_ID));
+        suite.env.block.time = Timestamp::from_seconds(now + 3_600);
+        let res = suite.liquify();
+        assert_eq!(reply_ids(&res).last(), Some(&BUYBACK_TRIGGER_REPLY_ID));
+    }
+
+    /// Pays keepers 1% of the liquified balance, at most once an hour
+    fn set_keeper_bounty(suite: &mut Suite) {
+        let admin = suite.admin.clone();
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::SetKeeperBounty {
+                    bounty: Some(KeeperBounty {
+                        reward: KeeperReward::Share {
+                            rate: Decimal::percent(1),
+                        },
+                        min_interval_seconds: 3_600,
+                    }),
+                },
+            )
+            .unwrap();
+    }
+
+    /// Amount of the reflection token transferred to the keeper by a response
+    fn keeper_bounty_transfer(suite: &Suite, res: &Response) -> Option<Uint128> {
+        res.messages.iter().find_map(|msg| {
+            let (contract, msg, _) = wasm_execute::<serde_json::Value>(msg);
+            match serde_json::from_value(msg).ok()? {
+                Cw20ExecuteMsg::Transfer { recipient, amount }
+                    if contract == suite.token.as_str() && recipient == suite.keeper.as_str() =>
+                {
+                    Some(amount)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    #[test]
+    fn test_keeper_bounty_paid_once_a_leg_succeeds() {
+        let mut suite = Suite::new();
+        let (token, reward) = (suite.token.clone(), suite.reward.clone());
+        set_keeper_bounty(&mut suite);
+        suite.set_cw20(&token, 100_000);
+
+        // The bounty is set aside before the split, nothing is paid yet
+        let res = suite.liquify();
+        assert_eq!(keeper_bounty_transfer(&suite, &res), None);
+        let outcome = suite.last_liquify();
+        assert_eq!(outcome.liquidity.amount, Uint128::new(69_300));
+        assert_eq!(outcome.reflection.amount, Uint128::new(19_800));
+        assert_eq!(outcome.burn.amount, Uint128::new(9_900));
+
+        let res = suite.reply(LIQUIDITY_SWAP_REPLY_ID, Some("swap failed"));
+        assert_eq!(keeper_bounty_transfer(&suite, &res), None);
+        suite.set_cw20(&reward, 39_600);
+        let res = suite.reply(REFLECTION_SWAP_REPLY_ID, None);
+        assert_eq!(
+            keeper_bounty_transfer(&suite, &res),
+            Some(Uint128::new(1_000))
+        );
+        assert!(!PENDING_KEEPER_BOUNTY.exists(&suite.deps.storage));
+
+        // Only the first leg that succeeds pays it
+        let res = suite.reply(BURN_REPLY_ID, None);
+        assert_eq!(keeper_bounty_transfer(&suite, &res), None);
+        assert_eq!(suite.stats().total_keeper_bounties, Uint128::new(1_000));
+        let now = suite.env.block.time.seconds();
+        let bounty: KeeperBountyResponse = suite.query(QueryMsg::KeeperBounty {});
+        assert_eq!(bounty.last_paid, Some(now));
+        assert_eq!(bounty.next_paid, Some(now + 3_600));
+    }
+
+    #[test]
+    fn test_keeper_bounty_not_paid_when_legs_fail() {
+        let mut suite = Suite::new();
+        let token = suite.token.clone();
+        set_keeper_bounty(&mut suite);
+        suite.set_cw20(&token, 100_000);
+
+        suite.liquify();
+        for id in [
+            LIQUIDITY_SWAP_REPLY_ID,
+            REFLECTION_SWAP_REPLY_ID,
+            BURN_REPLY_ID,
+        ] {
+            // Nor does the token restart the split of the balance the run left
+            let res = suite.reply(id, Some("leg failed"));
+            assert_eq!(keeper_bounty_transfer(&suite, &res), None);
+            assert!(!reports_liquified(&res));
+        }
+        assert_eq!(suite.stats().total_keeper_bounties, Uint128::zero());
+        let bounty: KeeperBountyResponse = suite.query(QueryMsg::KeeperBounty {});
+        assert_eq!(bounty.last_paid, None);
+
+        // The unpaid bounty stays in the treasury and the next keeper can earn it
+        let res = suite.liquify();
+        assert_eq!(keeper_bounty_transfer(&suite, &res), None);
+        assert!(!reports_liquified(&res));
+        let res = suite.reply(BURN_REPLY_ID, None);
+        assert_eq!(
+            keeper_bounty_transfer(&suite, &res),
+            Some(Uint128::new(1_000))
+        );
+        assert!(reports_liquified(&res));
+    }
+
+    #[test]
+    fn test_keeper_bounty_interval() {
+        let mut suite = Suite::new();
+        let token = suite.token.clone();
+        set_keeper_bounty(&mut suite);
+        suite.set_cw20(&token, 100_000);
+        let now = suite.env.block.time.seconds();
+
+        suite.liquify();
+        suite.reply(BURN_REPLY_ID, None);
+        assert_eq!(suite.stats().total_keeper_bounties, Uint128::new(1_000));
+
+        // Within the interval the whole balance is split and no bounty is set aside
+        suite.env.block.time = Timestamp::from_seconds(now + 3_599);
+        suite.liquify();
+        assert!(!PENDING_KEEPER_BOUNTY.exists(&suite.deps.storage));
+        assert_eq!(suite.last_liquify().liquidity.amount, Uint128::new(70_000));
+        let res = suite.reply(BURN_REPLY_ID, None);
+        assert_eq!(keeper_bounty_transfer(&suite, &res), None);
+
+        suite.env.block.time = Timestamp::from_seconds(now + 3_600);
+        suite.liquify();
+        let res = suite.reply(BURN_REPLY_ID, None);
+        assert_eq!(
+            keeper_bounty_transfer(&suite, &res),
+            Some(Uint128::new(1_000))
+        );
+        assert_eq!(suite.stats().total_keeper_bounties, Uint128::new(2_000));
+
+        // The token's own trigger is not paid
+        suite.env.block.time = Timestamp::from_seconds(now + 7_200);
+        let info = message_info(&token, &[]);
+        execute(
+            suite.deps.as_mut(),
+            suite.env.clone(),
+            info,
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: token.to_string(),
+                amount: Uint128::zero(),
+                msg: to_json_binary(&Cw20HookMsg::Liquify {}).unwrap(),
+            }),
+        )
+        .unwrap();
+        assert!(!PENDING_KEEPER_BOUNTY.exists(&suite.deps.storage));
+        assert_eq!(suite.last_liquify().liquidity.amount, Uint128::new(70_000));
+    }
+
+    #[test]
+    fn test_liquify_preview_keeper_bounty() {
+        let mut suite = Suite::new();
+        let token = suite.token.clone();
+        set_keeper_bounty(&mut suite);
+        suite.set_cw20(&token, 100_000);
+
+        // The bounty is only set aside for a keeper
+        for (keeper, bounty, liquidity) in [
+            (None, 0, 70_000),
+            (Some(token.to_string()), 0, 70_000),
+            (Some(suite.keeper.to_string()), 1_000, 69_300),
+        ] {
+            let preview: Option<LiquifyPreviewResponse> =
+                suite.query(QueryMsg::LiquifyPreview { keeper });
+            let preview = preview.unwrap();
+            assert_eq!(preview.keeper_bounty, Uint128::new(bounty));
+            assert_eq!(preview.liquidity.amount, Uint128::new(liquidity));
+        }
+    }
+
+    #[test]
+    fn test_liquify_preview_matches_execution() {
+        let mut suite = Suite::new();
+        let (token, lp) = (suite.token.clone(), suite.lp.clone());
+        suite.set_cw20(&token, 100_000);
+
+        let preview: Option<LiquifyPreviewResponse> =
+            suite.query(QueryMsg::LiquifyPreview { keeper: None });
+        let preview = preview.unwrap();
+        assert!(!preview.blocked);
+        assert_eq!(preview.liquidity.expected, Some(Uint128::new(70_000)));
+        assert_eq!(preview.reflection.expected, Some(Uint128::new(40_000)));
+        // The pair holds 1_035_000 token and 1_930_000 INJ after the swap
+        assert_eq!(preview.expected_lp, Some(Uint128::new(33_816)));
+
+        let res = suite.liquify();
+        let outcome = suite.last_liquify();
+        assert_eq!(outcome.liquidity.amount, preview.liquidity.amount);
+        assert_eq!(outcome.reflection.amount, preview.reflection.amount);
+        assert_eq!(outcome.burn.amount, preview.burn_amount);
+
+        let sent = |msg: &SubMsg| match wasm_execute::<Cw20ExecuteMsg>(msg).1 {
+            Cw20ExecuteMsg::Send {
+                contract,
+                amount,
+                msg,
+            } => (
+                contract,
+                amount,
+                from_json::<serde_json::Value>(msg).unwrap(),
+            ),
+            msg => panic!("Unexpected message {:?}", msg),
+        };
+        let (contract, amount, _) = sent(&res.messages[0]);
+        assert_eq!(contract, suite.pair.to_string());
+        assert_eq!(
+            amount,
+            preview.liquidity.amount.multiply_ratio(1u128, 2u128)
+        );
+        let (_, amount, hook) = sent(&res.messages[1]);
+        assert_eq!(amount, preview.reflection.amount);
+        // The reflection swap must return the simulated amount less the 5% default max spread
+        assert_eq!(
+            hook["execute_swap_operations"]["minimum_receive"],
+            serde_json::json!("38000")
+        );
+        let (_, msg, _) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[2]);
+        assert_eq!(
+            msg,
+            Cw20ExecuteMsg::Burn {
+                amount: preview.burn_amount
+            }
+        );
+
+        // A swap returning what was simulated provides the expected quote amount
+        suite.set_quote(preview.liquidity.expected.unwrap().u128());
+        let res = suite.reply(LIQUIDITY_SWAP_REPLY_ID, None);
+        let (_, _, funds) = wasm_execute::<serde_json::Value>(&res.messages[1]);
+        assert_eq!(
+            funds,
+            vec![coin(preview.liquidity.expected.unwrap().u128(), QUOTE)]
+        );
+        suite.set_cw20(&lp, preview.expected_lp.unwrap().u128());
+        suite.reply(PROVIDE_LIQUIDITY_REPLY_ID, None);
+        assert_eq!(suite.stats().total_lp_minted, preview.expected_lp.unwrap());
+    }
+
+    #[test]
+    fn test_liquify_preview_blocked() {
+        let mut suite = Suite::new();
+        let (admin, token) = (suite.admin.clone(), suite.token.clone());
+        suite.set_cw20(&token, 100_000);
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::SetMinLiquify {
+                    min_liquify_amt: Uint128::new(100_001),
+                },
+            )
+            .unwrap();
+
+        let preview: Option<LiquifyPreviewResponse> =
+            suite.query(QueryMsg::LiquifyPreview { keeper: None });
+        assert!(preview.unwrap().blocked);
+        let res = suite.liquify();
+        assert!(res.messages.is_empty());
+
+        // The preview reports a failing simulation instead of failing itself
+        suite.chain.fail_simulations = true;
+        suite.set_cw20(&token, 100_001);
+        let preview: Option<LiquifyPreviewResponse> =
+            suite.query(QueryMsg::LiquifyPreview { keeper: None });
+        let preview = preview.unwrap();
+        assert!(!preview.blocked);
+        assert!(preview.liquidity.error.unwrap().contains("no liquidity"));
+        assert!(preview.reflection.error.unwrap().contains("no liquidity"));
+        assert_eq!(preview.expected_lp, None);
+    }
+
+    #[test]
+    fn test_dividends_reserved_until_paid() {
+        let mut suite = Suite::new();
+        let (admin, token, reward) = (
+            suite.admin.clone(),
+            suite.token.clone(),
+            suite.reward.clone(),
+        );
+        let holder = suite.deps.api.addr_make("holder");
+        suite
+            .execute(&admin, ExecuteMsg::SetDividends { enabled: true })
+            .unwrap();
+        suite.set_cw20(&token, 100_000);
+        suite.set_cw20(&reward, 1_000);
+
+        // The acquired reward is reserved for the holders and handed to the token to spread
+        suite.liquify();
+        suite.set_cw20(&reward, 41_000);
+        let res = suite.reply(REFLECTION_SWAP_REPLY_ID, None);
+        let (contract, msg, _) = wasm_execute::<TokenExecuteMsg>(res.messages.last().unwrap());
+        assert_eq!(contract, token.to_string());
+        assert_eq!(
+            msg,
+            TokenExecuteMsg::DistributeDividends {
+                amount: Uint128::new(40_000)
+            }
+        );
+        let dividends: DividendsResponse = suite.query(QueryMsg::Dividends {});
+        assert_eq!(dividends.unclaimed, Uint128::new(40_000));
+        let holdings: HoldingsResponse = suite.query(QueryMsg::Holdings {});
+        let reward_holding = holdings.reward.unwrap();
+        assert_eq!(reward_holding.balance, Uint128::new(41_000));
+        assert_eq!(reward_holding.reserved, Uint128::new(40_000));
+
+        // The admin can only withdraw what is not reserved
+        let reward_info = AssetInfo::Token {
+            contract_addr: reward.to_string(),
+        };
+        let res = suite
+            .execute(
+                &admin,
+                ExecuteMsg::WithdrawToken {
+                    asset: reward_info.clone(),
+                },
+            )
+            .unwrap();
+        let (_, msg, _) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0]);
+        assert_eq!(
+            msg,
+            Cw20ExecuteMsg::Transfer {
+                recipient: admin.to_string(),
+                amount: Uint128::new(1_000),
+            }
+        );
+        suite.set_cw20(&reward, 40_000);
+
+        // Claims are paid by the token only, out of the reserve
+        let pay = |amount: u128| ExecuteMsg::PayDividend {
+            recipient: holder.to_string(),
+            amount: Uint128::new(amount),
+        };
+        let err = suite.execute(&admin, pay(15_000)).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let res = suite.execute(&token, pay(15_000)).unwrap();
+        let (contract, msg, _) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0]);
+        assert_eq!(contract, reward.to_string());
+        assert_eq!(
+            msg,
+            Cw20ExecuteMsg::Transfer {
+                recipient: holder.to_string(),
+                amount: Uint128::new(15_000),
+            }
+        );
+        suite.set_cw20(&reward, 25_000);
+        assert!(suite.execute(&token, pay(25_001)).is_err());
+
+        let holdings: HoldingsResponse = suite.query(QueryMsg::Holdings {});
+        assert_eq!(holdings.reward.unwrap().reserved, Uint128::new(25_000));
+        let err = suite
+            .execute(&admin, ExecuteMsg::WithdrawToken { asset: reward_info })
+            .unwrap_err();
+        assert!(err.to_string().contains("No CW20 balance to withdraw"));
+    }
+
+    #[test]
+    fn test_config_echoes_settings() {
+        let mut suite = Suite::new();
+        let admin = suite.admin.clone();
+        let router = suite.deps.api.addr_make("router");
+        let token_info = AssetInfo::Token {
+            contract_addr: suite.token.to_string(),
+        };
+        let reward_info = AssetInfo::Token {
+            contract_addr: suite.reward.to_string(),
+        };
+        let quote_info = AssetInfo::NativeToken {
+            denom: QUOTE.to_string(),
+        };
+        let slippage = SlippageConfig {
+            max_spread: Decimal::percent(3),
+            slippage_tolerance: Decimal::percent(2),
+            deadline_seconds: 60,
+        };
+        let reflection_route = vec![
+            hop(&token_info, &quote_info),
+            hop(&quote_info, &reward_info),
+        ];
+        let liquidity_route = vec![hop(&token_info, &quote_info)];
+
+        let config: ConfigResponse = suite.query(QueryMsg::Config {});
+        assert_eq!(config.admin, admin.to_string());
+        assert_eq!(config.token, suite.token.to_string());
+        assert_eq!(config.router, router.to_string());
+        assert_eq!(config.router_dex, Dex::Choice);
+        assert_eq!(config.liquidity_token, Some(suite.lp.to_string()));
+        assert_eq!(config.reward_asset, Some(reward_info.clone()));
+        assert_eq!(config.slippage, SlippageConfig::default());
+        assert_eq!(config.reflection_route, None);
+        assert_eq!(config.liquidity_route, None);
+
+        for msg in [
+            ExecuteMsg::SetSlippage {
+                config: slippage.clone(),
+            },
+            ExecuteMsg::SetReflectionRoute {
+                operations: Some(reflection_route.clone()),
+            },
+            ExecuteMsg::SetLiquidityRoute {
+                operations: Some(liquidity_route.clone()),
+            },
+            ExecuteMsg::SetMinLiquify {
+                min_liquify_amt: Uint128::new(5_000),
+            },
+        ] {
+            suite.execute(&admin, msg).unwrap();
+        }
+
+        // Moving the router and the liquidity pair to Astroport
+        for contract in [&router, &suite.pair] {
+            suite.chain.astroport.insert(contract.to_string());
+        }
+        suite.sync();
+        for msg in [
+            ExecuteMsg::SetRouter {
+                router: router.to_string(),
+                dex: Some(Dex::Astroport),
+            },
+            ExecuteMsg::SetLiquidityPair {
+                asset_infos: [token_info.clone(), quote_info.clone()],
+                pair_contract: suite.pair.to_string(),
+                dex: Some(Dex::Astroport),
+            },
+        ] {
+            suite.execute(&admin, msg).unwrap();
+        }
+
+        let config: ConfigResponse = suite.query(QueryMsg::Config {});
+        assert_eq!(config.router_dex, Dex::Astroport);
+        assert_eq!(
+            config.liquidity_pair.unwrap(),
+            PairConfig {
+                asset_infos: [token_info, quote_info],
+                contract: suite.pair.to_string(),
+                dex: Dex::Astroport,
+            }
+        );
+        assert_eq!(config.reflection_pair.unwrap().dex, Dex::Choice);
+        assert_eq!(config.min_liquify_amt, Uint128::new(5_000));
+        assert_eq!(config.slippage, slippage);
+        assert_eq!(config.reflection_route, Some(reflection_route));
+        assert_eq!(config.liquidity_route, Some(liquidity_route));
+    }
+
+    #[test]
+    fn test_holdings_report_reserved_amounts() {
+        let mut suite = Suite::new();
+        let (admin, token, lp, reward) = (
+            suite.admin.clone(),
+            suite.token.clone(),
+            suite.lp.clone(),
+            suite.reward.clone(),
+        );
+        let cw20 = |addr: &Addr| AssetInfo::Token {
+            contract_addr: addr.to_string(),
+        };
+        let holding = |asset: AssetInfo, balance: u128, reserved: u128| AssetHolding {
+            asset,
+            balance: Uint128::new(balance),
+            reserved: Uint128::new(reserved),
+        };
+        set_keeper_bounty(&mut suite);
+        for msg in [
+            ExecuteMsg::SetDividends { enabled: true },
+            ExecuteMsg::SetLpLock {
+                unlock_time: suite.env.block.time.seconds() + MIN_LP_LOCK_SECONDS,
+                release_duration: 1_000,
+            },
+        ] {
+            suite.execute(&admin, msg).unwrap();
+        }
+        suite.set_cw20(&token, 100_000);
+        suite.set_cw20(&lp, 2_000);
+        suite.set_cw20(&reward, 1_000);
+        suite.set_quote(5_000);
+
+        // While the legs run, the keeper's 1_000 bounty is held back from the admin
+        suite.liquify();
+        assert_eq!(
+            suite.query::<HoldingsResponse>(QueryMsg::Holdings {}),
+            HoldingsResponse {
+                token: holding(cw20(&token), 100_000, 1_000),
+                lp: Some(holding(cw20(&lp), 2_000, 2_000)),
+                reward: Some(holding(cw20(&reward), 1_000, 0)),
+                quote: Some(holding(
+                    AssetInfo::NativeToken {
+                        denom: QUOTE.to_string(),
+                    },
+                    5_000,
+                    0
+                )),
+            }
+        );
+        let res = suite
+            .execute(
+                &admin,
+                ExecuteMsg::WithdrawToken {
+                    asset: cw20(&token),
+                },
+            )
+            .unwrap();
+        let (_, msg, _) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0]);
+        assert_eq!(
+            msg,
+            Cw20ExecuteMsg::Transfer {
+                recipient: admin.to_string(),
+                amount: Uint128::new(99_000),
+            }
+        );
+
+        // Once paid the bounty is not reserved anymore, the acquired dividends are
+        suite.reply(LIQUIDITY_SWAP_REPLY_ID, Some("swap failed"));
+        suite.set_cw20(&reward, 41_000);
+        suite.reply(REFLECTION_SWAP_REPLY_ID, None);
+        let holdings: HoldingsResponse = suite.query(QueryMsg::Holdings {});
+        assert_eq!(holdings.token, holding(cw20(&token), 100_000, 0));
+        assert_eq!(
+            holdings.reward,
+            Some(holding(cw20(&reward), 41_000, 40_000))
+        );
+
+        // A bounty whose legs all failed is not owed
+        suite.env.block.time = suite.env.block.time.plus_seconds(3_600);
+        suite.liquify();
+        let holdings: HoldingsResponse = suite.query(QueryMsg::Holdings {});
+        assert_eq!(holdings.token.reserved, Uint128::new(1_000));
+        for id in [
+            LIQUIDITY_SWAP_REPLY_ID,
+            REFLECTION_SWAP_REPLY_ID,
+            BURN_REPLY_ID,
+        ] {
+            suite.reply(id, Some("leg failed"));
+        }
+        let holdings: HoldingsResponse = suite.query(QueryMsg::Holdings {});
+        assert_eq!(holdings.token.reserved, Uint128::zero());
+    }
+}
@@ -0,0 +1,315 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{to_json_binary, Binary, Decimal, QuerierWrapper, StdResult, Uint128};
+
+use crate::asset::{Asset, AssetInfo, PairInfo};
+use crate::pair::{
+    Cw20HookMsg as PairCw20HookMsg, ExecuteMsg as PairExecuteMsg, PoolResponse,
+    QueryMsg as PairQueryMsg, SimulationResponse,
+};
+use crate::router::{
+    ExecuteMsg as RouterExecuteMsg, QueryMsg as RouterQueryMsg, SimulateSwapOperationsResponse,
+    SwapOperation,
+};
+
+/// Message format of the AMM a pair or router belongs to
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Dex {
+    #[default]
+    Choice,
+    /// Astroport style pairs and routers, which have no deadline
+    Astroport,
+}
+
+/// Assets and LP token of a pair, whatever its DEX
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DexPairInfo {
+    pub asset_infos: Vec<AssetInfo>,
+    pub liquidity_token: String,
+}
+
+/// Reserves and LP supply of a pair, whatever its DEX
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DexPoolInfo {
+    pub assets: Vec<Asset>,
+    pub total_share: Uint128,
+}
+
+impl Dex {
+    pub fn query_pair(&self, querier: &QuerierWrapper, pair: &str) -> StdResult<DexPairInfo> {
+        match self {
+            Dex::Choice => {
+                let info: PairInfo = querier.query_wasm_smart(pair, &PairQueryMsg::Pair {})?;
+                Ok(DexPairInfo {
+                    asset_infos: info.asset_infos.to_vec(),
+                    liquidity_token: info.liquidity_token,
+                })
+            }
+            Dex::Astroport => {
+                let info: astroport::PairInfo =
+                    querier.query_wasm_smart(pair, &astroport::PairQueryMsg::Pair {})?;
+                Ok(DexPairInfo {
+                    asset_infos: info.asset_infos,
+                    liquidity_token: info.liquidity_token,
+                })
+            }
+        }
+    }
+
+    pub fn query_pool(&self, querier: &QuerierWrapper, pair: &str) -> StdResult<DexPoolInfo> {
+        match self {
+            Dex::Choice => {
+                let pool: PoolResponse = querier.query_wasm_smart(pair, &PairQueryMsg::Pool {})?;
+                Ok(DexPoolInfo {
+                    assets: pool.assets.to_vec(),
+                    total_share: pool.total_share,
+                })
+            }
+            Dex::Astroport => querier.query_wasm_smart(pair, &astroport::PairQueryMsg::Pool {}),
+        }
+    }
+
+    pub fn simulate(
+        &self,
+        querier: &QuerierWrapper,
+        pair: &str,
+        offer_asset: &Asset,
+    ) -> StdResult<SimulationResponse> {
+        match self {
+            Dex::Choice => querier.query_wasm_smart(
+                pair,
+                &PairQueryMsg::Simulation {
+                    offer_asset: offer_asset.clone(),
+                },
+            ),
+            Dex::Astroport => querier.query_wasm_smart(
+                pair,
+                &astroport::PairQueryMsg::Simulation {
+                    offer_asset: offer_asset.clone(),
+                    ask_asset_info: None,
+                },
+            ),
+        }
+    }
+
+    /// Hook of a CW20 `Send` swapping the sent token in a pair
+    pub fn swap_hook(
+        &self,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        deadline: Option<u64>,
+    ) -> StdResult<Binary> {
+        match self {
+            Dex::Choice => to_json_binary(&PairCw20HookMsg::Swap {
+                belief_price,
+                max_spread,
+                to: None,
+                deadline,
+            }),
+            Dex::Astroport => to_json_binary(&astroport::PairCw20HookMsg::Swap {
+                ask_asset_info: None,
+                belief_price,
+                max_spread,
+                to: None,
+            }),
+        }
+    }
+
+    /// Message swapping a native asset in a pair, the asset goes in the funds
+    pub fn swap_msg(
+        &self,
+        offer_asset: Asset,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        deadline: Option<u64>,
+    ) -> StdResult<Binary> {
+        match self {
+            Dex::Choice => to_json_binary(&PairExecuteMsg::Swap {
+                offer_asset,
+                belief_price,
+                max_spread,
+                to: None,
+                deadline,
+            }),
+            Dex::Astroport => to_json_binary(&astroport::PairExecuteMsg::Swap {
+                offer_asset,
+                ask_asset_info: None,
+                belief_price,
+                max_spread,
+                to: None,
+            }),
+        }
+    }
+
+    /// Message providing liquidity to a pair, CW20 assets need an allowance first
+    pub fn provide_liquidity_msg(
+        &self,
+        assets: [Asset; 2],
+        slippage_tolerance: Option<Decimal>,
+        deadline: Option<u64>,
+    ) -> StdResult<Binary> {
+        match self {
+            Dex::Choice => to_json_binary(&PairExecuteMsg::ProvideLiquidity {
+                assets,
+                receiver: None,
+                deadline,
+                slippage_tolerance,
+            }),
+            Dex::Astroport => to_json_binary(&astroport::PairExecuteMsg::ProvideLiquidity {
+                assets: assets.to_vec(),
+                slippage_tolerance,
+                auto_stake: None,
+                receiver: None,
+            }),
+        }
+    }
+
+    /// Amount a router returns for `offer_amount` sent through `operations`
+    pub fn simulate_route(
+        &self,
+        querier: &QuerierWrapper,
+        router: &str,
+        offer_amount: Uint128,
+        operations: &[SwapOperation],
+    ) -> StdResult<Uint128> {
+        let response: SimulateSwapOperationsResponse = match self {
+            Dex::Choice => querier.query_wasm_smart(
+                router,
+                &RouterQueryMsg::SimulateSwapOperations {
+                    offer_amount,
+                    operations: operations.to_vec(),
+                },
+            )?,
+            Dex::Astroport => querier.query_wasm_smart(
+                router,
+                &astroport::RouterQueryMsg::SimulateSwapOperations {
+                    offer_amount,
+                    operations: astroport::operations(operations),
+                },
+            )?,
+        };
+        Ok(response.amount)
+    }
+
+    /// Hook of a CW20 `Send` swapping the sent token through a router
+    pub fn route_hook(
+        &self,
+        operations: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        max_spread: Option<Decimal>,
+        deadline: Option<u64>,
+    ) -> StdResult<Binary> {
+        match self {
+            Dex::Choice => to_json_binary(&RouterExecuteMsg::ExecuteSwapOperations {
+                operations,
+                minimum_receive,
+                to: None,
+                deadline,
+            }),
+            Dex::Astroport => {
+                to_json_binary(&astroport::RouterCw20HookMsg::ExecuteSwapOperations {
+                    operations: astroport::operations(&operations),
+                    minimum_receive,
+                    to: None,
+                    max_spread,
+                })
+            }
+        }
+    }
+}
+
+/// Astroport message shapes
+mod astroport {
+    use cosmwasm_std::{Decimal, Uint128};
+    use serde::{Deserialize, Serialize};
+
+    use crate::asset::{Asset, AssetInfo};
+    use crate::router::SwapOperation as ChoiceSwapOperation;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum PairExecuteMsg {
+        ProvideLiquidity {
+            assets: Vec<Asset>,
+            slippage_tolerance: Option<Decimal>,
+            auto_stake: Option<bool>,
+            receiver: Option<String>,
+        },
+        Swap {
+            offer_asset: Asset,
+            ask_asset_info: Option<AssetInfo>,
+            belief_price: Option<Decimal>,
+            max_spread: Option<Decimal>,
+            to: Option<String>,
+        },
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum PairCw20HookMsg {
+        Swap {
+            ask_asset_info: Option<AssetInfo>,
+            belief_price: Option<Decimal>,
+            max_spread: Option<Decimal>,
+            to: Option<String>,
+        },
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum PairQueryMsg {
+        Pair {},
+        Pool {},
+        Simulation {
+            offer_asset: Asset,
+            ask_asset_info: Option<AssetInfo>,
+        },
+    }
+
+    #[derive(Deserialize)]
+    pub struct PairInfo {
+        pub asset_infos: Vec<AssetInfo>,
+        pub liquidity_token: String,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum SwapOperation {
+        AstroSwap {
+            offer_asset_info: AssetInfo,
+            ask_asset_info: AssetInfo,
+        },
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum RouterCw20HookMsg {
+        ExecuteSwapOperations {
+            operations: Vec<SwapOperation>,
+            minimum_receive: Option<Uint128>,
+            to: Option<String>,
+            max_spread: Option<Decimal>,
+        },
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum RouterQueryMsg {
+        SimulateSwapOperations {
+            offer_amount: Uint128,
+            operations: Vec<SwapOperation>,
+        },
+    }
+
+    pub fn operations(operations: &[ChoiceSwapOperation]) -> Vec<SwapOperation> {
+        operations
+            .iter()
+            .map(|operation| SwapOperation::AstroSwap {
+                offer_asset_info: operation.get_offer_asset_info(),
+                ask_asset_info: operation.get_target_asset_info(),
+            })
+            .collect()
+    }
+}
@@ -1,3 +1,4 @@
+pub mod adapter;
 pub mod asset;
 pub mod factory;
 pub mod pair;
@@ -7,10 +7,10 @@ use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::panic;
 
-use crate::asset::{AssetInfo, PairInfo};
+use crate::asset::{Asset, AssetInfo, PairInfo};
 use crate::factory::{NativeTokenDecimalsResponse, QueryMsg as FactoryQueryMsg};
 use crate::pair::QueryMsg as PairQueryMsg;
-use crate::pair::{ReverseSimulationResponse, SimulationResponse};
+use crate::pair::{PoolResponse, ReverseSimulationResponse, SimulationResponse};
 use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg, TokenInfoResponse};
 
 use std::iter::FromIterator;
@@ -35,6 +35,13 @@ pub struct WasmMockQuerier {
     base: MockQuerier,
     token_querier: TokenQuerier,
     choice_factory_querier: TerraswapFactoryQuerier,
+    pool_querier: PoolQuerier,
+}
+
+#[derive(Clone, Default)]
+pub struct PoolQuerier {
+    // pair contract address => pool reserves
+    pools: HashMap<String, [Asset; 2]>,
 }
 
 #[derive(Clone, Default)]
@@ -172,6 +179,22 @@ impl WasmMockQuerier {
                             liquidity_token: "liquidity0000".to_string(),
                         })))
                     }
+                    Ok(PairQueryMsg::Pool {}) => match self.pool_querier.pools.get(contract_addr) {
+                        Some(assets) => SystemResult::Ok(ContractResult::Ok(
+                            to_json_binary(&PoolResponse {
+                                assets: assets.clone(),
+                                total_share: Uint128::zero(),
+                            })
+                            .unwrap(),
+                        )),
+                        None => SystemResult::Err(SystemError::InvalidRequest {
+                            error: format!(
+                                "No pool info exists for the contract {}",
+                                contract_addr
+                            ),
+                            request: msg.as_slice().into(),
+                        }),
+                    },
                     Ok(PairQueryMsg::Simulation { offer_asset }) => SystemResult::Ok(
                         ContractResult::from(to_json_binary(&SimulationResponse {
                             return_amount: offer_asset.amount,
@@ -265,6 +288,7 @@ impl WasmMockQuerier {
             base,
             token_querier: TokenQuerier::default(),
             choice_factory_querier: TerraswapFactoryQuerier::default(),
+            pool_querier: PoolQuerier::default(),
         }
     }
 
@@ -282,6 +306,13 @@ impl WasmMockQuerier {
         self.choice_factory_querier = TerraswapFactoryQuerier::new(pairs, native_token_decimals);
     }
 
+    // configure the reserves returned by a pair's `Pool` query
+    pub fn with_pool(&mut self, pair_contract: &str, assets: [Asset; 2]) {
+        self.pool_querier
+            .pools
+            .insert(pair_contract.to_string(), assets);
+    }
+
     pub fn with_balance(&mut self, balances: &[(&String, Vec<Coin>)]) {
         for (addr, balance) in balances {
             self.base
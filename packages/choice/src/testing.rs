@@ -1,11 +1,13 @@
+use crate::adapter::Dex;
 use crate::asset::{Asset, AssetInfo, AssetInfoRaw, AssetRaw, PairInfo};
 use crate::mock_querier::mock_dependencies;
 use crate::querier::{query_balance, query_pair_info, query_token_balance, query_token_info};
+use crate::router::SwapOperation;
 
 use cosmwasm_std::testing::MOCK_CONTRACT_ADDR;
 use cosmwasm_std::{
-    coin, to_json_binary, Addr, Api, BankMsg, Coin, CosmosMsg, MessageInfo, StdError, SubMsg,
-    Uint128, WasmMsg,
+    coin, from_json, to_json_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal,
+    MessageInfo, StdError, SubMsg, Uint128, WasmMsg,
 };
 use cw20::Cw20ExecuteMsg;
 
@@ -408,3 +410,74 @@ fn query_choice_pair_contract() {
         Addr::unchecked("liquidity0000").to_string(),
     );
 }
+
+#[test]
+fn dex_adapter_message_formats() {
+    let json = |msg: Binary| from_json::<serde_json::Value>(&msg).unwrap();
+    let hop = SwapOperation::Choice {
+        offer_asset_info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        ask_asset_info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    };
+
+    assert_eq!(
+        json(
+            Dex::Choice
+                .swap_hook(None, Some(Decimal::percent(1)), Some(100))
+                .unwrap()
+        ),
+        serde_json::json!({"swap": {
+            "belief_price": null, "max_spread": "0.01", "to": null, "deadline": 100
+        }})
+    );
+    assert_eq!(
+        json(
+            Dex::Astroport
+                .swap_hook(None, Some(Decimal::percent(1)), Some(100))
+                .unwrap()
+        ),
+        serde_json::json!({"swap": {
+            "ask_asset_info": null, "belief_price": null, "max_spread": "0.01", "to": null
+        }})
+    );
+    assert_eq!(
+        json(
+            Dex::Astroport
+                .swap_msg(
+                    Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "uusd".to_string(),
+                        },
+                        amount: Uint128::new(5),
+                    },
+                    None,
+                    None,
+                    Some(100),
+                )
+                .unwrap()
+        ),
+        serde_json::json!({"swap": {
+            "offer_asset": {"info": {"native_token": {"denom": "uusd"}}, "amount": "5"},
+            "ask_asset_info": null, "belief_price": null, "max_spread": null, "to": null
+        }})
+    );
+    assert_eq!(
+        json(
+            Dex::Astroport
+                .route_hook(vec![hop], Some(Uint128::new(5)), None, Some(100))
+                .unwrap()
+        ),
+        serde_json::json!({"execute_swap_operations": {
+            "operations": [{"astro_swap": {
+                "offer_asset_info": {"token": {"contract_addr": "asset0000"}},
+                "ask_asset_info": {"native_token": {"denom": "uusd"}}
+            }}],
+            "minimum_receive": "5",
+            "to": null,
+            "max_spread": null
+        }})
+    );
+}